@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use actix_multipart::Multipart;
+use futures::{StreamExt, TryStreamExt};
+use serde_json::{json, Map, Value};
+
+use crate::audio::PcmFormat;
+use crate::errors::{AudioError, AudioResult};
+use crate::processors::normalize::{DEFAULT_SPLICE_COUNT, DEFAULT_SPLICE_DURATION};
+use crate::processors::{registry, ProcessorConfig, SpliceConfig};
+use crate::upload::UploadLimits;
+use crate::workspace::Workspace;
+
+/// Splice form fields and the `SpliceConfig` fields they set
+const SPLICE_FIELDS: &[(&str, &str)] = &[
+    ("spliceDuration", "duration"),
+    ("spliceCount", "count"),
+    ("reverse", "reverse"),
+    ("seed", "seed"),
+    ("clamp", "clamp"),
+    ("windowStart", "window_start"),
+    ("windowEnd", "window_end"),
+    ("zeroCrossingRadiusMs", "zero_crossing_radius_ms"),
+    ("minRmsDb", "min_rms_db"),
+    ("maxRedraws", "max_redraws"),
+    ("bars", "bars"),
+    ("beats", "beats"),
+    ("beatsPerBar", "beats_per_bar"),
+    ("bpm", "bpm"),
+];
+
+const FADE_FIELDS: &[(&str, &str)] = &[("fadeInMs", "in_ms"), ("fadeOutMs", "out_ms")];
+const FADE_CURVE_FIELDS: &[(&str, &str)] = &[("fadeCurve", "curve")];
+const GAP_FIELDS: &[(&str, &str)] = &[("minGapSeconds", "min_gap_seconds")];
+const ONSET_FIELDS: &[(&str, &str)] = &[("onsetSensitivity", "sensitivity"), ("minOnsetIntervalMs", "min_interval_ms")];
+const NORMALIZE_FIELDS: &[(&str, &str)] = &[("targetLevel", "target_level"), ("targetLufs", "target_lufs"), ("truePeak", "true_peak")];
+const LIMITER_FIELDS: &[(&str, &str)] = &[
+    ("limiterCeilingDb", "ceiling_db"),
+    ("limiterLookaheadMs", "lookahead_ms"),
+    ("limiterReleaseMs", "release_ms"),
+];
+const SILENCE_FIELDS: &[(&str, &str)] = &[
+    ("thresholdDb", "threshold_db"),
+    ("minSilenceMs", "min_silence_ms"),
+    ("minSegmentMs", "min_segment_ms"),
+    ("maxSegmentSeconds", "max_segment_seconds"),
+    ("paddingMs", "padding_ms"),
+];
+const SETTINGS_FIELDS: &[&[(&str, &str)]] = &[
+    SPLICE_FIELDS, FADE_FIELDS, FADE_CURVE_FIELDS, GAP_FIELDS, ONSET_FIELDS, NORMALIZE_FIELDS, LIMITER_FIELDS, SILENCE_FIELDS,
+];
+/// Fields read whole rather than through one of the settings tables
+const OTHER_FIELDS: &[&str] = &["outputFormat", "placement", "regions", "applyToSplices", "stages", "config"];
+
+/// Longest text field accepted; configs and region lists fit well within it
+const MAX_FIELD_BYTES: usize = 16 * 1024;
+
+/// Whether any endpoint reads the form field `name`
+fn is_known_field(name: &str) -> bool {
+    OTHER_FIELDS.contains(&name) || SETTINGS_FIELDS.iter().any(|table| table.iter().any(|&(field, _)| field == name))
+}
+
+/// A multipart upload: the `file` part saved as a workspace's input, and the fields endpoints read as text
+pub struct UploadForm {
+    size_bytes: Option<u64>,
+    fields: HashMap<String, String>,
+}
+
+impl UploadForm {
+    /// Read `payload`, streaming its `file` part into `workspace` within `limits`.
+    ///
+    /// Fields no endpoint reads are skipped unread, and a text field longer than
+    /// `MAX_FIELD_BYTES` fails with `AudioError::TooLarge`.
+    pub async fn read(payload: &mut Multipart, workspace: &Workspace, limits: &UploadLimits) -> AudioResult<Self> {
+        let mut form = Self { size_bytes: None, fields: HashMap::new() };
+
+        while let Some(mut field) = payload.try_next().await.map_err(|e| AudioError::ProcessingError(e.to_string()))? {
+            let Some(name) = field.content_disposition().and_then(|disposition| disposition.get_name()).map(str::to_string) else {
+                continue;
+            };
+            if name == "file" {
+                form.size_bytes = Some(limits.save(&mut field, &workspace.input_path()).await?);
+                continue;
+            }
+            if !is_known_field(&name) {
+                continue;
+            }
+
+            let mut value = Vec::new();
+            while let Some(chunk) = field.next().await {
+                value.extend_from_slice(&chunk.map_err(|e| AudioError::ProcessingError(e.to_string()))?);
+                if value.len() > MAX_FIELD_BYTES {
+                    return Err(AudioError::TooLarge(format!("the {} field is over the {} KB limit", name, MAX_FIELD_BYTES / 1024)));
+                }
+            }
+            let value = String::from_utf8(value)
+                .map_err(|_| AudioError::ProcessingError(format!("The {} field is not UTF-8 text", name)))?;
+            form.fields.insert(name, value);
+        }
+        Ok(form)
+    }
+
+    /// Size of the uploaded file, failing if the form had none
+    pub fn file_size(&self) -> AudioResult<u64> {
+        self.size_bytes.ok_or_else(|| AudioError::ProcessingError("Missing file field".to_string()))
+    }
+
+    /// The text of field `name`, if it was sent
+    pub fn text(&self, name: &str) -> Option<&str> {
+        debug_assert!(is_known_field(name), "form field {} is read but not listed as known", name);
+        self.fields.get(name).map(|value| value.trim())
+    }
+
+    /// The JSON in field `name`, if it was sent
+    pub fn json(&self, name: &str) -> AudioResult<Option<Value>> {
+        self.text(name)
+            .map(|text| serde_json::from_str(text).map_err(|e| AudioError::ProcessingError(format!("Invalid {}: {}", name, e))))
+            .transpose()
+    }
+
+    /// Config settings from the form fields in `fields`, each stored under the config field it is paired with.
+    ///
+    /// Values are taken as JSON where they parse, so numbers and booleans keep their
+    /// type, and as strings otherwise; the config's deserializer checks them.
+    fn settings(&self, fields: &[(&str, &str)]) -> Map<String, Value> {
+        fields.iter()
+            .filter_map(|&(field, key)| {
+                let text = self.text(field)?;
+                let value = serde_json::from_str(text).unwrap_or_else(|_| Value::from(text));
+                Some((key.to_string(), value))
+            })
+            .collect()
+    }
+
+    /// The `outputFormat` field, which accepts any case
+    fn output_format(&self) -> AudioResult<Option<Value>> {
+        let format = self.text("outputFormat").map(str::parse::<PcmFormat>).transpose()?;
+        Ok(format.map(|format| json!(format)))
+    }
+
+    /// A fade from `fadeInMs`, `fadeOutMs` and `fadeCurve`; none unless a length was given
+    fn fade(&self) -> Option<Value> {
+        let mut fade = self.settings(FADE_FIELDS);
+        if fade.is_empty() {
+            return None;
+        }
+        fade.extend(self.settings(FADE_CURVE_FIELDS));
+        Some(Value::Object(fade))
+    }
+
+    /// The placement named by `placement`, with `minGapSeconds` or the onset fields as its settings
+    fn placement(&self) -> AudioResult<Value> {
+        match self.text("placement").unwrap_or("random") {
+            "min_gap_seconds" => {
+                let gap = self.settings(GAP_FIELDS);
+                if gap.is_empty() {
+                    return Err(AudioError::InvalidDuration("minGapSeconds is required for min_gap_seconds placement".to_string()));
+                }
+                Ok(Value::Object(gap))
+            },
+            "onsets" => Ok(json!({
+                "onsets": self.settings(ONSET_FIELDS),
+            })),
+            name => Ok(Value::from(name)),
+        }
+    }
+
+    /// Splice settings from the splice endpoint's fields, before `outputFormat`
+    fn splice_settings(&self) -> AudioResult<Map<String, Value>> {
+        let mut splice = self.settings(SPLICE_FIELDS);
+        splice.entry("reverse").or_insert(Value::Bool(false));
+        splice.insert("placement".to_string(), self.placement()?);
+        if let Some(fade) = self.fade() {
+            splice.insert("fade".to_string(), fade);
+        }
+        Ok(splice)
+    }
+
+    /// The config sent to the splice endpoint; `regions` replaces random splice selection
+    pub fn splice_config(&self) -> AudioResult<ProcessorConfig> {
+        let mut settings = match self.json("regions")? {
+            Some(regions) => {
+                let mut settings = Map::new();
+                settings.insert("regions".to_string(), regions);
+                if let Some(fade) = self.fade() {
+                    settings.insert("fade".to_string(), fade);
+                }
+                settings
+            },
+            None => self.splice_settings()?,
+        };
+        if let Some(format) = self.output_format()? {
+            settings.insert("output_format".to_string(), format);
+        }
+        let name = if settings.contains_key("regions") { "regions" } else { "splice" };
+        registry::lookup(name)?.config_from_json(Value::Object(settings))
+    }
+
+    /// The config sent to the normalize endpoint; `applyToSplices` cuts splices as the splice endpoint would
+    pub fn normalize_config(&self) -> AudioResult<ProcessorConfig> {
+        let mut settings = self.settings(NORMALIZE_FIELDS);
+        // Default to 95% of maximum level
+        settings.entry("target_level").or_insert(json!(0.95));
+        if let Some(format) = self.output_format()? {
+            settings.insert("output_format".to_string(), format);
+        }
+
+        let limiter = self.settings(LIMITER_FIELDS);
+        if !limiter.is_empty() {
+            settings.insert("limiter".to_string(), Value::Object(limiter));
+        }
+
+        let apply_to_splices = self.text("applyToSplices").map_or(Ok(false), str::parse)
+            .map_err(|_| AudioError::ProcessingError("Invalid applyToSplices flag format".to_string()))?;
+        if apply_to_splices {
+            // The default splices, with whatever the form sets on top
+            let mut splice = json!(SpliceConfig::new(DEFAULT_SPLICE_DURATION, DEFAULT_SPLICE_COUNT));
+            if let Some(defaults) = splice.as_object_mut() {
+                defaults.extend(self.splice_settings()?);
+            }
+            settings.insert("splice".to_string(), splice);
+        }
+        registry::lookup("normalize")?.config_from_json(Value::Object(settings))
+    }
+
+    /// The config sent to the silence endpoint
+    pub fn silence_config(&self) -> AudioResult<ProcessorConfig> {
+        let mut settings = self.settings(SILENCE_FIELDS);
+        if let Some(format) = self.output_format()? {
+            settings.insert("output_format".to_string(), format);
+        }
+        if let Some(fade) = self.fade() {
+            settings.insert("fade".to_string(), fade);
+        }
+        registry::lookup("slice_by_silence")?.config_from_json(Value::Object(settings))
+    }
+
+    /// The config sent to the pipeline endpoint, either whole in `config` or as `stages` and `outputFormat`
+    pub fn pipeline_config(&self) -> AudioResult<ProcessorConfig> {
+        let settings = match self.json("config")? {
+            Some(config) => config,
+            None => {
+                let mut settings = Map::new();
+                settings.insert("stages".to_string(), self.json("stages")?.unwrap_or_else(|| json!([])));
+                if let Some(format) = self.output_format()? {
+                    settings.insert("output_format".to_string(), format);
+                }
+                Value::Object(settings)
+            },
+        };
+        registry::lookup("pipeline")?.config_from_json(settings)
+    }
+
+    /// The config in the `config` field for processor `name`; `{}` if it was left out,
+    /// for processors whose settings all have defaults
+    pub fn processor_config(&self, name: &str) -> AudioResult<ProcessorConfig> {
+        let config = self.json("config")?.unwrap_or_else(|| json!({}));
+        registry::lookup(name)?.config_from_json(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::error::PayloadError;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use actix_web::web::Bytes;
+    use actix_web::ResponseError;
+    use crate::dsp::fade::FadeCurve;
+    use crate::processors::Placement;
+    use crate::processors::placement::OnsetSettings;
+
+    fn form(fields: &[(&str, &str)]) -> UploadForm {
+        UploadForm {
+            size_bytes: None,
+            fields: fields.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_splice_fields_build_a_splice_config() {
+        let config = form(&[
+            ("spliceDuration", "0.5"),
+            ("spliceCount", "4"),
+            ("outputFormat", "Int16"),
+            ("seed", "7"),
+            ("placement", "onsets"),
+            ("onsetSensitivity", "0.8"),
+            ("fadeOutMs", " 10 "),
+            ("fadeCurve", "s_curve"),
+            ("bpm", "120"),
+        ]).splice_config().unwrap();
+
+        let ProcessorConfig::Splice(splice) = config else { panic!("expected a splice config") };
+        assert_eq!((splice.duration, splice.count, splice.reverse), (0.5, 4, false));
+        assert_eq!(splice.output_format, Some(PcmFormat::Int16));
+        assert_eq!(splice.seed, Some(7));
+        assert_eq!(splice.placement, Placement::Onsets(OnsetSettings { sensitivity: 0.8, ..Default::default() }));
+        assert_eq!(splice.fade.map(|fade| (fade.in_ms, fade.out_ms, fade.curve)), Some((0.0, 10.0, FadeCurve::SCurve)));
+        assert_eq!(splice.bpm, Some(120.0));
+
+        let regions = form(&[("regions", r#"[{"start": 0.0, "end": 1.0}]"#)]).splice_config().unwrap();
+        assert_eq!(regions.type_name(), "regions");
+
+        assert!(form(&[("spliceDuration", "long"), ("spliceCount", "4")]).splice_config().is_err());
+        assert!(form(&[("spliceDuration", "1"), ("spliceCount", "4"), ("placement", "min_gap_seconds")]).splice_config().is_err());
+    }
+
+    #[test]
+    fn test_normalize_fields_only_cut_splices_when_asked() {
+        let ProcessorConfig::Normalize { target_level, splice, limiter, .. } = form(&[("limiterCeilingDb", "-2")]).normalize_config().unwrap() else {
+            panic!("expected a normalize config")
+        };
+        assert_eq!(target_level, 0.95);
+        assert!(splice.is_none());
+        assert_eq!(limiter.unwrap().ceiling_db, -2.0);
+
+        let config = form(&[("applyToSplices", "true"), ("seed", "3"), ("outputFormat", "float32")]).normalize_config().unwrap();
+        let ProcessorConfig::Normalize { splice: Some(splice), output_format, .. } = config else {
+            panic!("expected normalized splices")
+        };
+        assert_eq!((splice.duration, splice.count, splice.seed), (DEFAULT_SPLICE_DURATION, DEFAULT_SPLICE_COUNT, Some(3)));
+        // The format is the normalize config's, not the splices'
+        assert_eq!((output_format, splice.output_format), (Some(PcmFormat::Float32), None));
+    }
+
+    /// A multipart payload with a text part for each of `fields`
+    fn payload(fields: &[(&str, &[u8])]) -> Multipart {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(format!("--BOUNDARY\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes());
+            body.extend_from_slice(value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--BOUNDARY--\r\n");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("multipart/form-data; boundary=BOUNDARY"));
+        Multipart::new(&headers, futures::stream::iter([Ok::<_, PayloadError>(Bytes::from(body))]))
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_fields_are_skipped_and_long_fields_rejected() {
+        let workspace = Workspace::create().unwrap();
+        let limits = UploadLimits { max_bytes: 1024, max_duration_secs: 60.0 };
+        let long = vec![b'x'; MAX_FIELD_BYTES + 1];
+
+        let form = UploadForm::read(&mut payload(&[("spliceCount", b"4"), ("notes", &long)]), &workspace, &limits).await.unwrap();
+        assert_eq!(form.text("spliceCount"), Some("4"));
+        assert!(!form.fields.contains_key("notes"));
+
+        let result = UploadForm::read(&mut payload(&[("config", &long)]), &workspace, &limits).await;
+        let error = result.err().unwrap();
+        assert!(matches!(error, AudioError::TooLarge(_)), "{:?}", error);
+        assert_eq!(error.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 use crate::processors::{ProcessorConfig, ProcessingResult};
 
+pub mod form;
 pub mod v1;
 
 #[derive(Debug, Deserialize)]
 pub struct ProcessAudioRequest {
+    pub asset_id: String,  // Returned by POST /api/v1/uploads
     pub config: ProcessorConfig,
 }
 
@@ -16,14 +18,6 @@ pub struct ProcessAudioResponse {
 }
 
 impl ProcessAudioResponse {
-    pub fn success(result: ProcessingResult) -> Self {
-        Self {
-            success: true,
-            result: Some(result),
-            error: None,
-        }
-    }
-
     pub fn error(message: String) -> Self {
         Self {
             success: false,
@@ -1,14 +1,21 @@
-use actix_web::{web, HttpResponse, Result, Error};
+use actix_web::{guard, web, HttpResponse, Result, Error};
+use actix_web::guard::GuardContext;
+use actix_web::http::header;
 use actix_multipart::Multipart;
-use futures::{StreamExt, TryStreamExt};
-use std::io::Write;
 use log::{info, error};
 use std::time::SystemTime;
 
-use crate::processors::{ProcessorConfig, splice::SpliceProcessor, normalize::NormalizeProcessor, AudioProcessor};
+use crate::audio::DecodeOptions;
+use crate::processors::registry;
+use crate::processors::{processor_for, AudioProcessor, ProcessorConfig, ProcessingMetadata};
+use crate::api::form::UploadForm;
 use crate::api::{ProcessAudioRequest, ProcessAudioResponse, HealthResponse, ErrorResponse};
-use crate::errors::AudioError;
-use crate::utils::create_zip_from_result;
+use crate::errors::{AudioError, AudioResult};
+use crate::jobs::{JobStatus, JobStore};
+use crate::pool::ProcessingPool;
+use crate::upload::{UploadLimits, UploadStore};
+use crate::utils::process_to_zip;
+use crate::workspace::Workspace;
 
 static START_TIME: std::sync::OnceLock<SystemTime> = std::sync::OnceLock::new();
 
@@ -16,12 +23,27 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/v1")
             .route("/health", web::get().to(health_check))
-            .route("/audio/splice", web::post().to(process_audio_json))
+            .route("/audio/splice", web::post().guard(guard::fn_guard(is_json)).to(process_audio_json))
             .route("/audio/splice/multipart", web::post().to(process_audio_multipart))
             .route("/audio/normalize/multipart", web::post().to(process_normalize_multipart))
+            .route("/audio/silence/multipart", web::post().to(process_silence_multipart))
+            .route("/audio/pipeline", web::post().to(process_pipeline_multipart))
+            // Any registered processor; the routes above take precedence for their paths
+            .route("/audio/{processor}", web::post().to(process_registered_multipart))
+            .route("/uploads", web::post().to(upload_audio))
+            .route("/jobs", web::post().to(submit_job_multipart))
+            .route("/jobs/{id}", web::get().to(job_status))
+            .route("/jobs/{id}/result", web::get().to(job_result))
     );
 }
 
+/// Whether the request body is JSON, so multipart posts to the same path reach the generic handler
+fn is_json(ctx: &GuardContext) -> bool {
+    ctx.head().headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
 pub fn init_start_time() {
     START_TIME.set(SystemTime::now()).ok();
 }
@@ -41,184 +63,167 @@ async fn health_check() -> Result<HttpResponse> {
     }))
 }
 
-async fn process_audio_json(req: web::Json<ProcessAudioRequest>) -> Result<HttpResponse> {
-    info!("Processing JSON audio request: {:?}", req.config);
-    
-    // For JSON requests, we need to handle file upload differently
-    // This is a simplified version - in a real implementation you'd want
-    // to use a different approach for file uploads with JSON
-    let error_response = ErrorResponse {
-        error: "JSON file upload not yet implemented. Use /audio/splice/multipart endpoint.".to_string(),
-        error_type: "NotImplemented".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-    };
-    
-    Ok(HttpResponse::NotImplemented().json(error_response))
-}
-
-async fn process_audio_multipart(mut payload: Multipart) -> Result<HttpResponse, Error> {
-    let file_path = "/tmp/received_audio.wav";
-    let output_dir = "/tmp/splices";
-    let mut splice_duration: f64 = 0.0;
-    let mut splice_count: i32 = 0;
-    let mut reverse: bool = false;
-
-    // Parse multipart data
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        let content_disposition = field.content_disposition();
-
-        if let Some(name) = content_disposition.expect("Invalid content disposition").get_name() {
-            match name {
-                "file" => {
-                    let mut f = std::fs::File::create(file_path)?;
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk.map_err(|e| AudioError::ProcessingError(e.to_string()))?;
-                        f.write_all(&data)?;
-                    }
-                },
-                "spliceDuration" => {
-                    let mut value = String::new();
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk.map_err(|e| AudioError::InvalidDuration(e.to_string()))?;
-                        value.push_str(std::str::from_utf8(&data).map_err(|e| AudioError::ProcessingError(e.to_string()))?);
-                    }
-                    splice_duration = value.parse().map_err(|_| AudioError::InvalidDuration("Invalid splice duration format".to_string()))?;
-                },
-                "spliceCount" => {
-                    let mut value = String::new();
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk?;
-                        value.push_str(std::str::from_utf8(&data)?);
-                    }
-                    splice_count = value.parse().map_err(|_| AudioError::InvalidSpliceCount("Invalid splice count format".to_string()))?;
-                },
-                "reverse" => {
-                    let mut value = String::new();
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk?;
-                        value.push_str(std::str::from_utf8(&data)?);
-                    }
-                    reverse = value.parse().map_err(|_| AudioError::ProcessingError("Invalid reverse flag format".to_string()))?;
-                },
-                _ => {}
-            }
-        }
+async fn process_audio_json(
+    pool: web::Data<ProcessingPool>,
+    limits: web::Data<UploadLimits>,
+    uploads: web::Data<UploadStore>,
+    req: web::Json<ProcessAudioRequest>,
+) -> Result<HttpResponse, Error> {
+    let ProcessAudioRequest { asset_id, config } = req.into_inner();
+    info!("Processing JSON audio request - Asset: {}, Config: {:?}", asset_id, config);
+
+    if !matches!(config, ProcessorConfig::Splice(_) | ProcessorConfig::Regions { .. }) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "This endpoint takes a splice or regions config".to_string(),
+            error_type: "InvalidConfig".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }));
     }
 
-    info!("Processing audio - File: {}, Duration: {}, Count: {}, Reverse: {}", 
-          file_path, splice_duration, splice_count, reverse);
+    let asset = uploads.get(&asset_id).ok_or_else(|| AudioError::FileNotFound(format!("No upload {}", asset_id)))?;
+    let workspace = Workspace::create()?;
+    asset.link_into(&workspace)?;
 
-    // Create config and process using the new architecture
-    let config = ProcessorConfig::Splice {
-        duration: splice_duration,
-        count: splice_count,
-        reverse,
-    };
+    Ok(run_and_zip(&pool, processor_for(&config)?, workspace, config, limits.decode_options()).await?)
+}
 
-    let processor = SpliceProcessor::new();
-    
-    match processor.process(file_path, output_dir, &config) {
-        Ok(result) => {
-            let zip_path = "/tmp/splices.zip";
-            
-            match create_zip_from_result(&result, zip_path) {
-                Ok(_) => {
-                    let file_contents = std::fs::read(zip_path)?;
-                    
-                    // Cleanup temporary files
-                    crate::utils::cleanup_temp_files(file_path, &result.files, zip_path);
-                    
-                    Ok(HttpResponse::Ok()
-                        .content_type("application/zip")
-                        .body(file_contents))
-                },
-                Err(e) => {
-                    error!("Failed to create ZIP: {}", e);
-                    Ok(HttpResponse::InternalServerError().json(ProcessAudioResponse::error(e.to_string())))
-                }
-            }
-        },
-        Err(e) => {
-            error!("Audio processing failed: {}", e);
-            Ok(HttpResponse::BadRequest().json(ProcessAudioResponse::error(e.to_string())))
-        }
-    }
+async fn upload_audio(
+    limits: web::Data<UploadLimits>,
+    uploads: web::Data<UploadStore>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, Error> {
+    let workspace = Workspace::create()?;
+    let size_bytes = UploadForm::read(&mut payload, &workspace, &limits).await?.file_size()?;
+
+    let asset = uploads.insert(workspace, size_bytes);
+    info!("Stored upload {} ({} bytes)", asset.asset_id, size_bytes);
+    Ok(HttpResponse::Created().json(asset))
 }
 
-async fn process_normalize_multipart(mut payload: Multipart) -> Result<HttpResponse, Error> {
-    let file_path = "/tmp/received_audio.wav";
-    let output_dir = "/tmp/normalized";
-    let mut target_level: f64 = 0.95;  // Default to 95% of maximum level
-    let mut apply_to_splices: bool = false;
-
-    // Parse multipart data
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        let content_disposition = field.content_disposition();
-
-        if let Some(name) = content_disposition.expect("Invalid content disposition").get_name() {
-            match name {
-                "file" => {
-                    let mut f = std::fs::File::create(file_path)?;
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk.map_err(|e| AudioError::ProcessingError(e.to_string()))?;
-                        f.write_all(&data)?;
-                    }
-                },
-                "targetLevel" => {
-                    let mut value = String::new();
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk.map_err(|e| AudioError::ProcessingError(e.to_string()))?;
-                        value.push_str(std::str::from_utf8(&data).map_err(|e| AudioError::ProcessingError(e.to_string()))?);
-                    }
-                    target_level = value.parse().map_err(|_| AudioError::ProcessingError("Invalid target level format".to_string()))?;
-                },
-                "applyToSplices" => {
-                    let mut value = String::new();
-                    while let Some(chunk) = field.next().await {
-                        let data = chunk?;
-                        value.push_str(std::str::from_utf8(&data)?);
-                    }
-                    apply_to_splices = value.parse().map_err(|_| AudioError::ProcessingError("Invalid applyToSplices flag format".to_string()))?;
-                },
-                _ => {}
-            }
-        }
+/// Build the ZIP download response, echoing the splice seed so a set can be reproduced
+fn zip_response(metadata: &ProcessingMetadata, file_contents: Vec<u8>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/zip");
+    if let Some(seed) = metadata.seed {
+        response.insert_header(("X-Splice-Seed", seed.to_string()));
     }
+    response.body(file_contents)
+}
+
+/// Process the upload in `workspace` on the pool, within the decode limits in `options`, and respond with the ZIP of the result.
+///
+/// Processing and zipping are CPU-bound, so they run on the pool rather than the
+/// calling worker. Failures keep their own status code: a bad format is a 415, audio
+/// past the duration limit a 413 and a failed write a 500.
+pub async fn run_and_zip(
+    pool: &ProcessingPool,
+    processor: Box<dyn AudioProcessor>,
+    workspace: Workspace,
+    config: ProcessorConfig,
+    options: DecodeOptions,
+) -> Result<HttpResponse, AudioError> {
+    let processor_type = processor.processor_type();
+    let (result, file_contents) = pool.run(move || process_to_zip(processor.as_ref(), &workspace, &config, options)).await?
+        .inspect_err(|e| error!("{} failed: {}", processor_type, e))?;
+    Ok(zip_response(&result.metadata, file_contents))
+}
+
+/// Save the upload in `payload`, build the config from its fields with `config_from_form`, then process and zip it.
+///
+/// The per-processor routes differ only in how their form fields map onto a config.
+async fn process_form(
+    pool: &ProcessingPool,
+    limits: &UploadLimits,
+    mut payload: Multipart,
+    config_from_form: impl FnOnce(&UploadForm) -> AudioResult<ProcessorConfig>,
+) -> Result<HttpResponse, Error> {
+    let workspace = Workspace::create()?;
+    let form = UploadForm::read(&mut payload, &workspace, limits).await?;
+    form.file_size()?;
+    let config = config_from_form(&form)?;
+    info!("Processing {} - Config: {:?}", config.type_name(), config);
+
+    Ok(run_and_zip(pool, processor_for(&config)?, workspace, config, limits.decode_options()).await?)
+}
+
+async fn process_audio_multipart(pool: web::Data<ProcessingPool>, limits: web::Data<UploadLimits>, payload: Multipart) -> Result<HttpResponse, Error> {
+    process_form(&pool, &limits, payload, UploadForm::splice_config).await
+}
 
-    info!("Processing normalize - Target level: {}, Apply to splices: {}", target_level, apply_to_splices);
+async fn process_normalize_multipart(pool: web::Data<ProcessingPool>, limits: web::Data<UploadLimits>, payload: Multipart) -> Result<HttpResponse, Error> {
+    process_form(&pool, &limits, payload, UploadForm::normalize_config).await
+}
+
+async fn process_silence_multipart(pool: web::Data<ProcessingPool>, limits: web::Data<UploadLimits>, payload: Multipart) -> Result<HttpResponse, Error> {
+    process_form(&pool, &limits, payload, UploadForm::silence_config).await
+}
+
+async fn process_pipeline_multipart(pool: web::Data<ProcessingPool>, limits: web::Data<UploadLimits>, payload: Multipart) -> Result<HttpResponse, Error> {
+    process_form(&pool, &limits, payload, UploadForm::pipeline_config).await
+}
 
-    // Create config and process using the normalize processor
-    let config = ProcessorConfig::Normalize {
-        target_level,
-        apply_to_splices,
+async fn submit_job_multipart(pool: web::Data<ProcessingPool>, limits: web::Data<UploadLimits>, jobs: web::Data<JobStore>, mut payload: Multipart) -> Result<HttpResponse, Error> {
+    let workspace = Workspace::create()?;
+    let form = UploadForm::read(&mut payload, &workspace, &limits).await?;
+    form.file_size()?;
+
+    let Some(config) = form.json("config")? else {
+        return Ok(HttpResponse::BadRequest().json(ProcessAudioResponse::error("Missing config field".to_string())));
     };
+    let config: ProcessorConfig = serde_json::from_value(config)
+        .map_err(|e| AudioError::ProcessingError(format!("Invalid config: {}", e)))?;
+    // Reject bad settings now rather than in a job that is bound to fail
+    processor_for(&config)?.validate_config(&config)?;
+
+    // Jobs wait their turn on the same pool as direct requests, and are turned away when it is full
+    let slot = pool.reserve()?;
+    let id = jobs.submit(workspace, config)?;
+    info!("Queued job {}", id);
+
+    let (worker_jobs, worker_id) = (jobs.clone(), id.clone());
+    let (failed_jobs, failed_id) = (jobs.clone(), id.clone());
+    let options = limits.decode_options();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = slot.run(move || worker_jobs.run(&worker_id, options)).await {
+            // Otherwise a job whose worker never ran would stay queued forever
+            error!("Job worker failed: {}", e);
+            failed_jobs.fail(&failed_id, &e);
+        }
+    });
+
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", format!("/api/v1/jobs/{}", id)))
+        .json(jobs.get(&id)))
+}
 
-    let processor = NormalizeProcessor::new();
-    
-    match processor.process(file_path, output_dir, &config) {
-        Ok(result) => {
-            let zip_path = "/tmp/normalized.zip";
-            
-            match create_zip_from_result(&result, zip_path) {
-                Ok(_) => {
-                    let file_contents = std::fs::read(zip_path)?;
-                    
-                    // Cleanup temporary files
-                    crate::utils::cleanup_temp_files(file_path, &result.files, zip_path);
-                    
-                    Ok(HttpResponse::Ok()
-                        .content_type("application/zip")
-                        .body(file_contents))
-                },
-                Err(e) => {
-                    error!("Failed to create ZIP: {}", e);
-                    Ok(HttpResponse::InternalServerError().json(ProcessAudioResponse::error(e.to_string())))
-                }
-            }
+async fn job_status(jobs: web::Data<JobStore>, id: web::Path<String>) -> Result<HttpResponse, Error> {
+    let job = jobs.get(&id).ok_or_else(|| AudioError::FileNotFound(format!("No job {}", id)))?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+async fn job_result(jobs: web::Data<JobStore>, id: web::Path<String>) -> Result<HttpResponse, Error> {
+    let job = jobs.get(&id).ok_or_else(|| AudioError::FileNotFound(format!("No job {}", id)))?;
+
+    match (job.zip_path(), &job.metadata) {
+        (Some(zip_path), Some(metadata)) => {
+            let file_contents = std::fs::read(&zip_path)?;
+            Ok(zip_response(metadata, file_contents))
         },
-        Err(e) => {
-            error!("Audio normalization failed: {}", e);
-            Ok(HttpResponse::BadRequest().json(ProcessAudioResponse::error(e.to_string())))
-        }
+        _ if job.status == JobStatus::Failed => {
+            let message = job.error.unwrap_or_else(|| "Job failed".to_string());
+            Ok(HttpResponse::Conflict().json(ProcessAudioResponse::error(message)))
+        },
+        _ => Ok(HttpResponse::Conflict().json(ProcessAudioResponse::error(format!("Job {} has not finished yet", job.id)))),
     }
-}
\ No newline at end of file
+}
+
+async fn process_registered_multipart(
+    pool: web::Data<ProcessingPool>,
+    limits: web::Data<UploadLimits>,
+    processor: web::Path<String>,
+    payload: Multipart,
+) -> Result<HttpResponse, Error> {
+    // Unknown names are a 404 before anything is uploaded
+    let registration = registry::lookup(&processor)?;
+    process_form(&pool, &limits, payload, |form| form.processor_config(registration.name)).await
+}
@@ -0,0 +1,306 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use hound::{SampleFormat, WavReader};
+use log::{info, warn};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::sample::SampleFormat as SymphoniaSampleFormat;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::errors::{AudioError, AudioResult};
+use super::{AudioBuffer, PcmFormat};
+use super::format::int_to_f32;
+use super::opus::codecs;
+
+/// Limits applied while decoding a source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecodeOptions {
+    /// Longest audio, in seconds, the source may decode to; unlimited when `None`
+    pub max_duration_secs: Option<f64>,
+}
+
+/// Container formats recognised from the first bytes of an upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerFormat {
+    Wav,
+    Mp3,
+    Adts,
+    Mp4,
+    Flac,
+    Ogg,
+    Unknown,
+}
+
+impl ContainerFormat {
+    /// Identify the container from its magic bytes
+    pub fn sniff(header: &[u8]) -> Self {
+        match header {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => ContainerFormat::Wav,
+            [b'f', b'L', b'a', b'C', ..] => ContainerFormat::Flac,
+            [b'O', b'g', b'g', b'S', ..] => ContainerFormat::Ogg,
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => ContainerFormat::Mp4,
+            [b'I', b'D', b'3', ..] => ContainerFormat::Mp3,
+            // ADTS sync word: 12 set bits followed by layer == 00
+            [0xFF, b, ..] if b & 0xF6 == 0xF0 => ContainerFormat::Adts,
+            // MPEG audio frame sync: 11 set bits with a valid layer
+            [0xFF, b, ..] if b & 0xE0 == 0xE0 && b & 0x06 != 0 => ContainerFormat::Mp3,
+            _ => ContainerFormat::Unknown,
+        }
+    }
+
+    /// File extension passed to symphonia's probe as a hint
+    fn extension(&self) -> Option<&'static str> {
+        match self {
+            ContainerFormat::Wav => Some("wav"),
+            ContainerFormat::Mp3 => Some("mp3"),
+            ContainerFormat::Adts => Some("aac"),
+            ContainerFormat::Mp4 => Some("m4a"),
+            ContainerFormat::Flac => Some("flac"),
+            ContainerFormat::Ogg => Some("ogg"),
+            ContainerFormat::Unknown => None,
+        }
+    }
+}
+
+/// An uploaded audio file of any supported container, decoded on demand to PCM.
+///
+/// WAV files are read with hound; MP3, AAC (ADTS or M4A/MP4), FLAC and Ogg Vorbis
+/// are decoded with symphonia, and Ogg Opus with ropus through symphonia's Ogg demuxer.
+#[derive(Debug)]
+pub struct AudioSource {
+    path: PathBuf,
+    format: ContainerFormat,
+    options: DecodeOptions,
+}
+
+impl AudioSource {
+    /// Open `path` and sniff its container format
+    pub fn open(path: &Path) -> AudioResult<Self> {
+        let mut header = [0u8; 16];
+        let mut file = File::open(path).map_err(AudioError::IoError)?;
+        let read = file.read(&mut header).map_err(AudioError::IoError)?;
+        let format = ContainerFormat::sniff(&header[..read]);
+
+        info!("Opened audio source {:?} - Detected format: {:?}", path, format);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            options: DecodeOptions::default(),
+        })
+    }
+
+    /// Decode with `options` instead of the defaults, which set no limits
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Fail with `AudioError::TooLarge` if `frames` at `sample_rate` runs past the limit
+    fn check_duration(&self, frames: u64, sample_rate: u32) -> AudioResult<()> {
+        match self.options.max_duration_secs {
+            Some(limit) if sample_rate > 0 && frames as f64 / sample_rate as f64 > limit => Err(AudioError::TooLarge(
+                format!("audio is longer than the {:.0} second limit", limit)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode the whole source into memory
+    pub fn decode(&self) -> AudioResult<AudioBuffer> {
+        match self.format {
+            ContainerFormat::Wav => self.decode_wav(),
+            _ => self.decode_compressed(),
+        }
+    }
+
+    fn decode_wav(&self) -> AudioResult<AudioBuffer> {
+        let mut reader = WavReader::open(&self.path).map_err(AudioError::WavError)?;
+        let spec = reader.spec();
+        let format = PcmFormat::from_spec(&spec)?;
+        // The header gives the length, so nothing is read when it is too long
+        self.check_duration(reader.duration() as u64, spec.sample_rate)?;
+
+        let samples: Result<Vec<f32>, _> = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect(),
+            SampleFormat::Int => reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| int_to_f32(s, spec.bits_per_sample)))
+                .collect(),
+        };
+
+        let mut samples = samples.map_err(AudioError::WavError)?;
+        // Drop a trailing partial frame from a truncated file
+        samples.truncate(samples.len() - samples.len() % spec.channels.max(1) as usize);
+
+        Ok(AudioBuffer {
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            format,
+            samples,
+        })
+    }
+
+    fn decode_compressed(&self) -> AudioResult<AudioBuffer> {
+        let file = File::open(&self.path).map_err(AudioError::IoError)?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = self.format.extension() {
+            hint.with_extension(extension);
+        }
+
+        let probed = symphonia::default::get_probe()
+            .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(|e| AudioError::InvalidFormat(format!("Unrecognised audio container: {}", e)))?;
+        let mut reader = probed.format;
+
+        let track = reader
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| AudioError::InvalidFormat("No audio track found".to_string()))?;
+
+        // Lossless sources keep their bit depth on output; lossy ones default to 16-bit
+        let format = match (track.codec_params.sample_format, track.codec_params.bits_per_sample) {
+            (Some(SymphoniaSampleFormat::F32), _) => PcmFormat::Float32,
+            (_, Some(bits)) => PcmFormat::from_int_bits(bits).unwrap_or(PcmFormat::Int16),
+            _ => PcmFormat::Int16,
+        };
+
+        let track_id = track.id;
+        let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+        let mut channels = track.codec_params.channels.map(|c| c.count() as u16).unwrap_or(0);
+        let mut decoder = codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        // Containers that state their length are turned away before decoding; others are cut off once they run over
+        if let Some(frames) = track.codec_params.n_frames {
+            self.check_duration(frames, sample_rate)?;
+        }
+        let mut samples: Vec<f32> = Vec::new();
+
+        loop {
+            let packet = match reader.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(e)) => {
+                    // A corrupt packet is skipped rather than failing the whole file
+                    warn!("Skipping undecodable packet: {}", e);
+                    continue;
+                },
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            sample_rate = spec.rate;
+            channels = spec.channels.count() as u16;
+
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+            self.check_duration((samples.len() / channels.max(1) as usize) as u64, sample_rate)?;
+        }
+
+        if sample_rate == 0 || channels == 0 {
+            return Err(AudioError::DecodeError("Could not determine sample rate or channel count".to_string()));
+        }
+
+        Ok(AudioBuffer {
+            sample_rate,
+            channels,
+            format,
+            samples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::Workspace;
+
+    /// Half a second of a 440 Hz tone at half scale, encoded in each compressed format
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn test_sniff_recognises_each_container() {
+        let cases: [(&[u8], ContainerFormat); 9] = [
+            (b"RIFF\x24\x08\x00\x00WAVEfmt ", ContainerFormat::Wav),
+            (b"fLaC\x00\x00\x00\x22", ContainerFormat::Flac),
+            (b"OggS\x00\x02", ContainerFormat::Ogg),
+            (b"\x00\x00\x00\x20ftypM4A ", ContainerFormat::Mp4),
+            (b"ID3\x04\x00", ContainerFormat::Mp3),
+            (&[0xFF, 0xF1, 0x50, 0x80], ContainerFormat::Adts),
+            (&[0xFF, 0xFB, 0x90, 0x64], ContainerFormat::Mp3),
+            (b"RIFF\x24\x08\x00\x00AVI LIST", ContainerFormat::Unknown),
+            (b"", ContainerFormat::Unknown),
+        ];
+        for (header, expected) in cases {
+            assert_eq!(ContainerFormat::sniff(header), expected, "header {:02x?}", header);
+        }
+    }
+
+    #[test]
+    fn test_each_fixture_decodes_to_the_tone() {
+        let cases = [
+            ("tone.mp3", ContainerFormat::Mp3, 44_100, 1),
+            ("tone.flac", ContainerFormat::Flac, 44_100, 2),
+            ("tone.ogg", ContainerFormat::Ogg, 44_100, 1),
+            ("tone.m4a", ContainerFormat::Mp4, 44_100, 1),
+            ("tone.opus", ContainerFormat::Ogg, 48_000, 2),
+        ];
+        for (name, format, sample_rate, channels) in cases {
+            let source = AudioSource::open(&fixture(name)).unwrap();
+            assert_eq!(source.format, format, "{}", name);
+
+            let buffer = source.decode().unwrap();
+            assert_eq!(buffer.sample_rate, sample_rate, "{}", name);
+            assert_eq!(buffer.channels, channels, "{}", name);
+            // Lossy encoders add up to a few frames of padding around the tone
+            assert!((0.49..0.65).contains(&buffer.duration()), "{} lasts {}s", name, buffer.duration());
+            let peak = buffer.samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            assert!((0.45..0.55).contains(&peak), "{} peaks at {}", name, peak);
+        }
+    }
+
+    #[test]
+    fn test_lossless_fixture_keeps_its_exact_length() {
+        let buffer = AudioSource::open(&fixture("tone.flac")).unwrap().decode().unwrap();
+        assert_eq!(buffer.frame_count(), 22_050);
+        assert_eq!(buffer.format, PcmFormat::Int16);
+    }
+
+    #[test]
+    fn test_opus_pre_skip_is_dropped() {
+        // The tone first passes 0.05 on its third sample; the 312 warm-up frames would push that back
+        let buffer = AudioSource::open(&fixture("tone.opus")).unwrap().decode().unwrap();
+        let onset = buffer.samples.iter().position(|s| s.abs() > 0.05).unwrap() / buffer.channels as usize;
+        assert!((1..10).contains(&onset), "tone starts at frame {}", onset);
+    }
+
+    #[test]
+    fn test_unknown_bytes_are_an_invalid_format() {
+        let workspace = Workspace::create().unwrap();
+        std::fs::write(workspace.input_path(), b"this is a text file, not audio").unwrap();
+
+        let source = AudioSource::open(&workspace.input_path()).unwrap();
+        assert_eq!(source.format, ContainerFormat::Unknown);
+        assert!(matches!(source.decode(), Err(AudioError::InvalidFormat(_))));
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::str::FromStr;
+use hound::{SampleFormat, WavSpec};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AudioError, AudioResult};
+
+/// PCM sample encodings that can be read from and written to WAV files.
+///
+/// Audio is processed internally as `f32` in the range -1.0..=1.0; a `PcmFormat`
+/// describes how samples are stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PcmFormat {
+    Int8,
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl PcmFormat {
+    /// The format matching a WAV header
+    pub fn from_spec(spec: &WavSpec) -> AudioResult<Self> {
+        match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Float, 32) => Ok(PcmFormat::Float32),
+            (SampleFormat::Float, bits) => Err(AudioError::InvalidFormat(format!("{}-bit float WAV is not supported", bits))),
+            (SampleFormat::Int, bits) => Self::from_int_bits(bits as u32),
+        }
+    }
+
+    /// The narrowest integer format that holds `bits` bits per sample
+    pub fn from_int_bits(bits: u32) -> AudioResult<Self> {
+        match bits {
+            1..=8 => Ok(PcmFormat::Int8),
+            9..=16 => Ok(PcmFormat::Int16),
+            17..=24 => Ok(PcmFormat::Int24),
+            25..=32 => Ok(PcmFormat::Int32),
+            _ => Err(AudioError::InvalidFormat(format!("{}-bit integer audio is not supported", bits))),
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            PcmFormat::Int8 => 8,
+            PcmFormat::Int16 => 16,
+            PcmFormat::Int24 => 24,
+            PcmFormat::Int32 | PcmFormat::Float32 => 32,
+        }
+    }
+
+    /// WAV header for writing audio in this format
+    pub fn wav_spec(&self, channels: u16, sample_rate: u32) -> WavSpec {
+        WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: self.bits_per_sample(),
+            sample_format: match self {
+                PcmFormat::Float32 => SampleFormat::Float,
+                _ => SampleFormat::Int,
+            },
+        }
+    }
+}
+
+impl fmt::Display for PcmFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PcmFormat::Int8 => "int8",
+            PcmFormat::Int16 => "int16",
+            PcmFormat::Int24 => "int24",
+            PcmFormat::Int32 => "int32",
+            PcmFormat::Float32 => "float32",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for PcmFormat {
+    type Err = AudioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "int8" => Ok(PcmFormat::Int8),
+            "int16" => Ok(PcmFormat::Int16),
+            "int24" => Ok(PcmFormat::Int24),
+            "int32" => Ok(PcmFormat::Int32),
+            "float32" => Ok(PcmFormat::Float32),
+            other => Err(AudioError::InvalidFormat(format!(
+                "Unknown output format '{}', expected one of int8, int16, int24, int32, float32", other
+            ))),
+        }
+    }
+}
+
+/// Convert an integer sample with the given bit depth to -1.0..=1.0
+pub fn int_to_f32(sample: i32, bits: u16) -> f32 {
+    (sample as f64 / (1i64 << (bits - 1)) as f64) as f32
+}
+
+/// Convert a -1.0..=1.0 sample to an integer of the given bit depth, clamping overs
+pub fn f32_to_int(sample: f32, bits: u16) -> i32 {
+    let scale = (1i64 << (bits - 1)) as f64;
+    (sample as f64 * scale).round().clamp(-scale, scale - 1.0) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioBuffer, AudioSource};
+    use crate::workspace::Workspace;
+
+    const INT_BITS: [u16; 4] = [8, 16, 24, 32];
+
+    /// Smallest and largest values an integer sample of `bits` bits can hold
+    fn int_range(bits: u16) -> (i32, i32) {
+        let max = ((1i64 << (bits - 1)) - 1) as i32;
+        (-max - 1, max)
+    }
+
+    #[test]
+    fn test_full_scale_integers_map_to_the_unit_range() {
+        for bits in INT_BITS {
+            let (min, max) = int_range(bits);
+            assert_eq!(int_to_f32(min, bits), -1.0, "{}-bit", bits);
+            assert_eq!(int_to_f32(0, bits), 0.0, "{}-bit", bits);
+            assert!((0.99..=1.0).contains(&int_to_f32(max, bits)), "{}-bit", bits);
+
+            assert_eq!(f32_to_int(-1.0, bits), min, "{}-bit", bits);
+            assert_eq!(f32_to_int(0.0, bits), 0, "{}-bit", bits);
+            // +1.0 is one step past the largest integer and clips to it
+            assert_eq!(f32_to_int(1.0, bits), max, "{}-bit", bits);
+        }
+    }
+
+    #[test]
+    fn test_integers_round_trip_through_f32() {
+        for bits in INT_BITS {
+            let (min, max) = int_range(bits);
+            // f32 keeps 24 bits of precision, so 32-bit samples may move by half of a 2^8 step
+            let tolerance = if bits > 24 { 1 << 7 } else { 0 };
+            let step = ((max as i64 - min as i64) / 1000).max(1) as usize;
+            for sample in (min as i64..=max as i64).step_by(step).chain([max as i64]) {
+                let sample = sample as i32;
+                let back = f32_to_int(int_to_f32(sample, bits), bits);
+                assert!(back.abs_diff(sample) <= tolerance, "{}-bit {} came back as {}", bits, sample, back);
+            }
+        }
+    }
+
+    #[test]
+    fn test_overs_clip_to_full_scale() {
+        for bits in INT_BITS {
+            let (min, max) = int_range(bits);
+            for over in [1.0001, 1.5, 100.0, f32::INFINITY] {
+                assert_eq!(f32_to_int(over, bits), max, "{}-bit {}", bits, over);
+                assert_eq!(f32_to_int(-over, bits), min, "{}-bit {}", bits, -over);
+            }
+        }
+    }
+
+    #[test]
+    fn test_each_format_round_trips_through_a_wav_file() {
+        let samples = vec![-1.5, -1.0, -0.5, -0.25, 0.0, 0.25, 0.5, 0.75, 1.0, 1.5];
+        for format in [PcmFormat::Int8, PcmFormat::Int16, PcmFormat::Int24, PcmFormat::Int32, PcmFormat::Float32] {
+            let workspace = Workspace::create().unwrap();
+            let buffer = AudioBuffer { sample_rate: 8000, channels: 1, format, samples: samples.clone() };
+            buffer.write_wav(&workspace.input_path(), format).unwrap();
+
+            let decoded = AudioSource::open(&workspace.input_path()).unwrap().decode().unwrap();
+            assert_eq!(decoded.format, format);
+            // Float keeps overs; integer formats clip them to full scale
+            let expected: Vec<f32> = match format {
+                PcmFormat::Float32 => samples.clone(),
+                _ => {
+                    let bits = format.bits_per_sample();
+                    let largest = int_to_f32(int_range(bits).1, bits);
+                    samples.iter().map(|s| s.clamp(-1.0, largest)).collect()
+                },
+            };
+            assert_eq!(decoded.samples, expected, "{}", format);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+use hound::WavWriter;
+
+use crate::errors::{AudioError, AudioResult};
+
+pub mod decode;
+pub mod format;
+mod opus;
+
+pub use decode::{AudioSource, DecodeOptions};
+pub use format::PcmFormat;
+
+use format::f32_to_int;
+
+/// Decoded PCM audio held in memory.
+///
+/// Samples are `f32` in the range -1.0..=1.0 and interleaved (`L R L R ...` for
+/// stereo). A frame is one sample per channel at a single point in time; all
+/// positions and lengths should be expressed in frames so channels never get
+/// split or swapped. `format` records how the source was encoded so output can
+/// be written back at the same bit depth.
+#[derive(Debug, Clone)]
+pub struct AudioBuffer {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: PcmFormat,
+    pub samples: Vec<f32>,
+}
+
+impl AudioBuffer {
+    /// Number of frames (samples per channel)
+    pub fn frame_count(&self) -> usize {
+        if self.channels == 0 {
+            return 0;
+        }
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Length of the audio in seconds
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.frame_count() as f64 / self.sample_rate as f64
+    }
+
+    /// Number of frames covering `seconds` of audio at this sample rate
+    pub fn frames_for(&self, seconds: f64) -> usize {
+        (seconds * self.sample_rate as f64).round().max(0.0) as usize
+    }
+
+    /// Copy `len` frames starting at frame `start`, truncated at the end of the buffer
+    pub fn slice_frames(&self, start: usize, len: usize) -> AudioBuffer {
+        let channels = self.channels as usize;
+        let start = start.min(self.frame_count());
+        let end = start.saturating_add(len).min(self.frame_count());
+        self.with_samples(self.samples[start * channels..end * channels].to_vec())
+    }
+
+    /// Append silence until the buffer is at least `frames` long
+    pub fn pad_to_frames(&mut self, frames: usize) {
+        let len = frames * self.channels as usize;
+        if self.samples.len() < len {
+            self.samples.resize(len, 0.0);
+        }
+    }
+
+    /// Reverse the order of frames, keeping the channel order within each frame
+    pub fn reverse_frames(&mut self) {
+        let channels = self.channels.max(1) as usize;
+        // Reversing all samples reverses the channels inside each frame too, so
+        // flip each frame back afterwards
+        self.samples.reverse();
+        for frame in self.samples.chunks_exact_mut(channels) {
+            frame.reverse();
+        }
+    }
+
+    /// A new buffer with the same rate, channels and format but different samples
+    pub fn with_samples(&self, samples: Vec<f32>) -> AudioBuffer {
+        AudioBuffer {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            format: self.format,
+            samples,
+        }
+    }
+
+    /// Write the buffer to a WAV file, encoding samples as `format`
+    pub fn write_wav(&self, path: &Path, format: PcmFormat) -> AudioResult<()> {
+        let spec = format.wav_spec(self.channels, self.sample_rate);
+        let mut writer = WavWriter::create(path, spec).map_err(AudioError::WavError)?;
+
+        for &sample in &self.samples {
+            let result = match format {
+                PcmFormat::Int8 => writer.write_sample(f32_to_int(sample, 8) as i8),
+                PcmFormat::Int16 => writer.write_sample(f32_to_int(sample, 16) as i16),
+                PcmFormat::Int24 => writer.write_sample(f32_to_int(sample, 24)),
+                PcmFormat::Int32 => writer.write_sample(f32_to_int(sample, 32)),
+                PcmFormat::Float32 => writer.write_sample(sample),
+            };
+            result.map_err(AudioError::WavError)?;
+        }
+
+        writer.finalize().map_err(AudioError::WavError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer whose sample at (frame, channel) is `frame * 10 + channel`
+    fn numbered_buffer(channels: u16, frames: usize) -> AudioBuffer {
+        let samples = (0..frames)
+            .flat_map(|frame| (0..channels).map(move |channel| (frame * 10 + channel as usize) as f32))
+            .collect();
+        AudioBuffer {
+            sample_rate: 100,
+            channels,
+            format: PcmFormat::Int16,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_duration_counts_frames_not_samples() {
+        for channels in [1, 2, 6] {
+            let buffer = numbered_buffer(channels, 250);
+            assert_eq!(buffer.frame_count(), 250);
+            assert_eq!(buffer.duration(), 2.5);
+        }
+    }
+
+    #[test]
+    fn test_slice_frames_mono() {
+        let slice = numbered_buffer(1, 10).slice_frames(3, 4);
+        assert_eq!(slice.samples, vec![30.0, 40.0, 50.0, 60.0]);
+    }
+
+    #[test]
+    fn test_slice_frames_stereo() {
+        let slice = numbered_buffer(2, 10).slice_frames(3, 2);
+        assert_eq!(slice.frame_count(), 2);
+        assert_eq!(slice.samples, vec![30.0, 31.0, 40.0, 41.0]);
+    }
+
+    #[test]
+    fn test_slice_frames_multichannel_truncates_at_end() {
+        let slice = numbered_buffer(6, 10).slice_frames(8, 5);
+        assert_eq!(slice.frame_count(), 2);
+        assert_eq!(&slice.samples[..6], &[80.0, 81.0, 82.0, 83.0, 84.0, 85.0]);
+    }
+
+    #[test]
+    fn test_reverse_frames_mono() {
+        let mut buffer = numbered_buffer(1, 3);
+        buffer.reverse_frames();
+        assert_eq!(buffer.samples, vec![20.0, 10.0, 0.0]);
+    }
+
+    #[test]
+    fn test_reverse_frames_stereo_keeps_channel_order() {
+        let mut buffer = numbered_buffer(2, 3);
+        buffer.reverse_frames();
+        assert_eq!(buffer.samples, vec![20.0, 21.0, 10.0, 11.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_reverse_frames_multichannel_keeps_channel_order() {
+        let mut buffer = numbered_buffer(6, 2);
+        buffer.reverse_frames();
+        assert_eq!(buffer.samples, vec![10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_decoding_stops_at_the_duration_limit() {
+        let workspace = crate::workspace::Workspace::create().unwrap();
+        let buffer = AudioBuffer { samples: vec![0.25; 500], ..numbered_buffer(2, 0) };
+        buffer.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let source = AudioSource::open(&workspace.input_path()).unwrap();
+        let source = source.with_options(DecodeOptions { max_duration_secs: Some(2.5) });
+        assert_eq!(source.decode().unwrap().duration(), 2.5);
+        let source = source.with_options(DecodeOptions { max_duration_secs: Some(2.0) });
+        assert!(matches!(source.decode(), Err(AudioError::TooLarge(_))));
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::OnceLock;
+use ropus::{Channels, DecodeMode};
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, CodecRegistry, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia::core::errors::{decode_error, unsupported_error, Error, Result};
+use symphonia::core::formats::Packet;
+use symphonia::core::support_codec;
+
+/// Opus always decodes at 48 kHz, whatever rate the source was recorded at
+const SAMPLE_RATE: u32 = 48_000;
+/// The longest Opus packet is 120 ms
+const MAX_PACKET_FRAMES: usize = 5_760;
+
+/// symphonia's built-in codecs plus Opus, which symphonia can demux from Ogg but not decode
+pub fn codecs() -> &'static CodecRegistry {
+    static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<OpusDecoder>();
+        registry
+    })
+}
+
+/// Adapts ropus to symphonia's `Decoder` trait so Ogg Opus goes through the same packet loop as every other codec.
+///
+/// Only mono and stereo streams (channel mapping family 0) are supported.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    channels: Channels,
+    decoder: ropus::Decoder,
+    /// Encoder warm-up frames at the start of the stream, which are not part of the audio
+    pre_skip: usize,
+    /// Warm-up frames still to be dropped from the next packets
+    skip_remaining: usize,
+    /// Interleaved output of the last packet, before it is split into planes
+    interleaved: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+impl OpusDecoder {
+    fn new_decoder(channels: Channels) -> Result<ropus::Decoder> {
+        ropus::Decoder::new(SAMPLE_RATE, channels).map_err(|_| Error::DecodeError("opus: could not create decoder"))
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: invalid codec type");
+        }
+
+        // The OpusHead packet holds the channel count at byte 9, pre-skip at 10..12 and the mapping family at byte 18.
+        // symphonia's Ogg reader derives its own delay from granule positions, so pre-skip is read here instead
+        let head = params.extra_data.as_deref().unwrap_or_default();
+        let channels = match (head.get(9), head.get(18)) {
+            (Some(1), Some(0)) => Channels::Mono,
+            (Some(2), Some(0)) => Channels::Stereo,
+            _ => return unsupported_error("opus: only mono and stereo streams are supported"),
+        };
+        let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+        let spec_channels = params.channels.ok_or(Error::Unsupported("opus: unknown channel layout"))?;
+
+        Ok(OpusDecoder {
+            params: params.clone(),
+            channels,
+            decoder: Self::new_decoder(channels)?,
+            pre_skip,
+            skip_remaining: pre_skip,
+            interleaved: vec![0.0; MAX_PACKET_FRAMES * channels.count()],
+            buf: AudioBuffer::new(MAX_PACKET_FRAMES as u64, SignalSpec::new(SAMPLE_RATE, spec_channels)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        if let Ok(decoder) = Self::new_decoder(self.channels) {
+            self.decoder = decoder;
+        }
+        self.skip_remaining = self.pre_skip;
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let frames = match self.decoder.decode_float(&packet.data, &mut self.interleaved, DecodeMode::Normal) {
+            Ok(frames) => frames,
+            Err(_) => return decode_error("opus: invalid packet"),
+        };
+
+        let skip = self.skip_remaining.min(frames);
+        self.skip_remaining -= skip;
+
+        let channel_count = self.channels.count();
+        self.buf.render_reserved(Some(frames - skip));
+        for (channel, plane) in self.buf.planes_mut().planes().iter_mut().enumerate() {
+            for (frame, sample) in plane.iter_mut().enumerate() {
+                *sample = self.interleaved[(skip + frame) * channel_count + channel];
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        Default::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
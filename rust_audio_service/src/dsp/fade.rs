@@ -0,0 +1,161 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioBuffer;
+use crate::errors::{AudioError, AudioResult};
+
+/// Shape of a fade, as gain against progress through the fade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Quarter sine; keeps perceived loudness steady when overlapped with the opposite fade
+    EqualPower,
+    /// Linear in decibels over a 60 dB range, so the fade sounds even to the ear
+    Exponential,
+    /// Raised cosine; slow at both ends and fastest in the middle
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Gain for a fade-in `progress` of 0.0 (silent) to 1.0 (full level)
+    pub fn gain(&self, progress: f64) -> f64 {
+        let x = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => x,
+            FadeCurve::EqualPower => (x * FRAC_PI_2).sin(),
+            FadeCurve::Exponential => {
+                // 10^(-3(1 - x)) runs from -60 dB to 0 dB; shift and rescale so it starts at exactly zero
+                let floor = 1e-3;
+                (10f64.powf(-3.0 * (1.0 - x)) - floor) / (1.0 - floor)
+            },
+            FadeCurve::SCurve => 0.5 - 0.5 * (x * PI).cos(),
+        }
+    }
+}
+
+impl FromStr for FadeCurve {
+    type Err = AudioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "linear" => Ok(FadeCurve::Linear),
+            "equal_power" => Ok(FadeCurve::EqualPower),
+            "exponential" => Ok(FadeCurve::Exponential),
+            "s_curve" => Ok(FadeCurve::SCurve),
+            other => Err(AudioError::ProcessingError(format!(
+                "Unknown fade curve '{}', expected one of linear, equal_power, exponential, s_curve", other
+            ))),
+        }
+    }
+}
+
+/// Fade-in and fade-out applied to the ends of each output file
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Fade {
+    #[serde(default)]
+    pub in_ms: f64,
+    #[serde(default)]
+    pub out_ms: f64,
+    #[serde(default)]
+    pub curve: FadeCurve,  // Used for both ends; the fade-out is the mirror image
+}
+
+impl Fade {
+    pub fn validate(&self) -> AudioResult<()> {
+        if !self.in_ms.is_finite() || !self.out_ms.is_finite() || self.in_ms < 0.0 || self.out_ms < 0.0 {
+            return Err(AudioError::InvalidDuration("fade lengths must be zero or positive".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Apply the fades in place.
+    ///
+    /// If the fades are longer than the audio they are shortened in proportion so
+    /// they meet without overlapping.
+    pub fn apply(&self, audio: &mut AudioBuffer) {
+        let frames = audio.frame_count();
+        let mut in_frames = audio.frames_for(self.in_ms / 1000.0);
+        let mut out_frames = audio.frames_for(self.out_ms / 1000.0);
+
+        // Huge fades saturate `frames_for`, so the sum is taken in floating point
+        if in_frames.saturating_add(out_frames) > frames {
+            let scale = frames as f64 / (in_frames as f64 + out_frames as f64);
+            in_frames = (in_frames as f64 * scale).floor() as usize;
+            out_frames = frames - in_frames;
+        }
+
+        let channels = audio.channels.max(1) as usize;
+        for (i, frame) in audio.samples.chunks_exact_mut(channels).enumerate() {
+            let mut gain = 1.0;
+            if i < in_frames {
+                gain *= self.curve.gain(i as f64 / in_frames as f64);
+            }
+            if i + out_frames >= frames {
+                gain *= self.curve.gain((frames - 1 - i) as f64 / out_frames as f64);
+            }
+            if gain != 1.0 {
+                for sample in frame {
+                    *sample = (*sample as f64 * gain) as f32;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    fn ones(channels: u16, frames: usize) -> AudioBuffer {
+        AudioBuffer {
+            sample_rate: 1000,
+            channels,
+            format: PcmFormat::Float32,
+            samples: vec![1.0; channels as usize * frames],
+        }
+    }
+
+    #[test]
+    fn test_curves_run_from_silence_to_full() {
+        for curve in [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::Exponential, FadeCurve::SCurve] {
+            assert!(curve.gain(0.0).abs() < 1e-12, "{:?}", curve);
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-12, "{:?}", curve);
+            assert!(curve.gain(0.25) < curve.gain(0.75), "{:?}", curve);
+        }
+        assert!((FadeCurve::EqualPower.gain(0.5) - 0.5f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fades_touch_only_the_ends_of_every_channel() {
+        let mut audio = ones(2, 100);
+        Fade { in_ms: 10.0, out_ms: 20.0, curve: FadeCurve::Linear }.apply(&mut audio);
+
+        assert_eq!(&audio.samples[..4], &[0.0, 0.0, 0.1, 0.1]);
+        assert_eq!(audio.samples[20], 1.0);
+        assert_eq!(audio.samples[159], 1.0);
+        assert_eq!(&audio.samples[198..], &[0.0, 0.0]);
+        assert_eq!(&audio.samples[160..162], &[0.95, 0.95]);
+    }
+
+    #[test]
+    fn test_overlong_fades_are_shortened_to_fit() {
+        let mut audio = ones(1, 10);
+        Fade { in_ms: 50.0, out_ms: 50.0, curve: FadeCurve::SCurve }.apply(&mut audio);
+
+        assert_eq!(audio.samples[0], 0.0);
+        assert_eq!(audio.samples[9], 0.0);
+        assert!(audio.samples.iter().all(|&s| (0.0..=1.0).contains(&s)));
+
+        // Lengths too large for a frame count are shortened the same way
+        let mut audio = ones(1, 10);
+        Fade { in_ms: 1e30, out_ms: 1e30, curve: FadeCurve::Linear }.apply(&mut audio);
+
+        assert_eq!(audio.samples[0], 0.0);
+        assert_eq!(audio.samples[9], 0.0);
+        assert!(audio.samples.iter().all(|&s| (0.0..1.0).contains(&s)));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::audio::AudioBuffer;
+
+/// Convert a linear amplitude (1.0 = full scale) to dBFS
+pub fn amplitude_to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+/// Convert dB to a linear gain
+pub fn db_to_amplitude(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// Root mean square of `frames` frames starting at `start`, over all channels together.
+///
+/// Returns 0.0 for an empty range.
+pub fn rms(audio: &AudioBuffer, start: usize, frames: usize) -> f64 {
+    let channels = audio.channels as usize;
+    let end = start.saturating_add(frames).min(audio.frame_count());
+    let start = start.min(end);
+    let samples = &audio.samples[start * channels..end * channels];
+
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum_of_squares: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+    (sum_of_squares / samples.len() as f64).sqrt()
+}
+
+/// RMS level in dBFS; digital silence is `f64::NEG_INFINITY`
+pub fn rms_db(audio: &AudioBuffer, start: usize, frames: usize) -> f64 {
+    amplitude_to_db(rms(audio, start, frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    #[test]
+    fn test_rms_db() {
+        let audio = AudioBuffer {
+            sample_rate: 10,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: vec![0.0, 0.0, 0.0, 0.0, 0.5, -0.5, -0.5, 0.5],
+        };
+        assert_eq!(rms_db(&audio, 0, 2), f64::NEG_INFINITY);
+        assert!((rms_db(&audio, 2, 2) - amplitude_to_db(0.5)).abs() < 1e-9);
+        assert!((rms_db(&audio, 2, 2) + 6.0206).abs() < 1e-4);
+        assert!((rms(&audio, 0, 4) - 0.5 / 2f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioBuffer;
+use crate::errors::{AudioError, AudioResult};
+use super::level::db_to_amplitude;
+use super::true_peak::peak_envelope;
+
+/// Longest lookahead accepted, since the limiter buffers that much audio ahead of the gain
+const MAX_LOOKAHEAD_MS: f64 = 500.0;
+
+/// Lookahead peak limiter; every field is optional
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limiter {
+    pub ceiling_db: f64,  // Highest true peak let through, in dBTP
+    pub lookahead_ms: f64,  // How far ahead the gain starts coming down before a peak
+    pub release_ms: f64,  // Time constant for the gain to recover after a peak
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -1.0,
+            lookahead_ms: 5.0,
+            release_ms: 100.0,
+        }
+    }
+}
+
+impl Limiter {
+    pub fn validate(&self) -> AudioResult<()> {
+        if !self.ceiling_db.is_finite() || self.ceiling_db > 0.0 {
+            return Err(AudioError::ProcessingError("limiter ceiling_db must be 0 dBTP or below".to_string()));
+        }
+        if !self.lookahead_ms.is_finite() || !self.release_ms.is_finite() || self.lookahead_ms <= 0.0 || self.release_ms < 0.0 {
+            return Err(AudioError::InvalidDuration(
+                "limiter lookahead_ms must be positive and release_ms zero or positive".to_string()
+            ));
+        }
+        if self.lookahead_ms > MAX_LOOKAHEAD_MS {
+            return Err(AudioError::InvalidDuration(format!(
+                "limiter lookahead_ms must be at most {} ms", MAX_LOOKAHEAD_MS
+            )));
+        }
+        Ok(())
+    }
+
+    /// Limit `audio` in place so its true peak stays at or below the ceiling.
+    ///
+    /// The gain each frame needs is worked out from the oversampled peak envelope.
+    /// It is held for the lookahead, released exponentially, then smoothed with a
+    /// moving average as long as the lookahead, so the gain ramps down ahead of a
+    /// peak instead of jumping. Every window the average covers sees the peak, so
+    /// the ceiling is still met. All channels share one gain to keep the image
+    /// steady. Returns the largest gain reduction in dB.
+    pub fn apply(&self, audio: &mut AudioBuffer) -> f64 {
+        let ceiling = db_to_amplitude(self.ceiling_db);
+        let lookahead = audio.frames_for(self.lookahead_ms / 1000.0).max(1);
+        let release = audio.frames_for(self.release_ms / 1000.0);
+
+        // Padded with `lookahead` unity frames in front so the first frames can be ramped into too
+        let needed: Vec<f64> = std::iter::repeat_n(1.0, lookahead)
+            .chain(peak_envelope(audio).into_iter().map(|peak| if peak > ceiling { ceiling / peak } else { 1.0 }))
+            .collect();
+        if needed.iter().all(|&gain| gain == 1.0) {
+            return 0.0;
+        }
+
+        let held = forward_min(&needed, lookahead + 1);
+        let recovery = if release == 0 { 0.0 } else { (-1.0 / release as f64).exp() };
+        let mut gain = 1.0;
+        let released: Vec<f64> = held
+            .into_iter()
+            .map(|hold| {
+                gain = hold.min(1.0 - (1.0 - gain) * recovery);
+                gain
+            })
+            .collect();
+
+        let channels = audio.channels.max(1) as usize;
+        let window = lookahead + 1;
+        let mut sum: f64 = released[..lookahead].iter().sum();
+        let mut lowest = 1.0f64;
+        for (frame, samples) in audio.samples.chunks_exact_mut(channels).enumerate() {
+            sum += released[frame + lookahead];
+            let smoothed = sum / window as f64;
+            sum -= released[frame];
+
+            lowest = lowest.min(smoothed);
+            for sample in samples {
+                *sample = (*sample as f64 * smoothed) as f32;
+            }
+        }
+
+        -20.0 * lowest.log10()
+    }
+}
+
+/// Minimum of each `window` values starting at every index, using a monotonic queue
+fn forward_min(values: &[f64], window: usize) -> Vec<f64> {
+    let mut minimums = vec![0.0; values.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+
+    for i in (0..values.len()).rev() {
+        while queue.back().is_some_and(|&j| values[j] >= values[i]) {
+            queue.pop_back();
+        }
+        queue.push_back(i);
+        while queue.front().is_some_and(|&j| j >= i + window) {
+            queue.pop_front();
+        }
+        minimums[i] = values[queue[0]];
+    }
+
+    minimums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+    use crate::audio::PcmFormat;
+    use crate::dsp::true_peak::true_peak_db;
+
+    #[test]
+    fn test_hot_bursts_are_held_under_the_ceiling() {
+        // Quiet 440 Hz tone with a burst at twice full scale in the middle
+        let sample_rate = 44100;
+        let mut audio = AudioBuffer {
+            sample_rate,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: (0..sample_rate as usize)
+                .flat_map(|n| {
+                    let level = if (20000..24000).contains(&n) { 2.0 } else { 0.25 };
+                    let sample = (level * (2.0 * PI * 440.0 * n as f64 / sample_rate as f64).sin()) as f32;
+                    [sample, -sample]
+                })
+                .collect(),
+        };
+        let untouched = audio.samples[..2 * 10000].to_vec();
+
+        let limiter = Limiter::default();
+        let reduction = limiter.apply(&mut audio);
+
+        assert!(reduction > 6.0, "{} dB", reduction);
+        assert!(true_peak_db(&audio) <= -1.0 + 0.05, "{} dBTP", true_peak_db(&audio));
+        // Well before the burst the tone passes through unchanged
+        assert_eq!(&audio.samples[..2 * 10000], &untouched[..]);
+    }
+
+    #[test]
+    fn test_lookahead_is_bounded() {
+        assert!(Limiter { lookahead_ms: MAX_LOOKAHEAD_MS, ..Default::default() }.validate().is_ok());
+
+        for lookahead_ms in [0.0, MAX_LOOKAHEAD_MS + 1.0, 1e12, f64::NAN] {
+            let result = Limiter { lookahead_ms, ..Default::default() }.validate();
+            assert!(matches!(result, Err(AudioError::InvalidDuration(_))), "{}", lookahead_ms);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::f64::consts::PI;
+
+use crate::audio::AudioBuffer;
+
+/// Blocks quieter than this are ignored entirely, in LUFS
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks more than this many LU below the level of the loud blocks are ignored
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+/// Integrated loudness of `audio` in LUFS, per ITU-R BS.1770-4 / EBU R128.
+///
+/// Each channel is K-weighted (a high shelf modelling the head plus a low cut),
+/// then the mean square is taken over 400 ms blocks overlapping by 75%. Blocks
+/// below -70 LUFS, and then those more than 10 LU below the average of the rest,
+/// are gated out so silence and quiet passages don't drag the result down. Six
+/// channels are taken to be 5.1, with the LFE ignored and the surrounds weighted
+/// +1.5 dB; other layouts weight every channel equally. Returns `None` for audio
+/// shorter than one block or with nothing above the absolute gate.
+pub fn integrated_loudness(audio: &AudioBuffer) -> Option<f64> {
+    let channels = audio.channels as usize;
+    let step = audio.frames_for(0.1).max(1);
+    let block = step * 4;
+    let steps = audio.frame_count() / step;
+    if channels == 0 || steps < 4 {
+        return None;
+    }
+
+    // Channel-weighted sum of squared K-weighted samples in each 100 ms step
+    let mut step_energy = vec![0.0f64; steps];
+    for channel in 0..channels {
+        let weight = channel_weight(channels, channel);
+        if weight == 0.0 {
+            continue;
+        }
+        let mut filter = KWeighting::new(audio.sample_rate);
+        for (frame, &sample) in audio.samples.iter().skip(channel).step_by(channels).enumerate().take(steps * step) {
+            let weighted = filter.process(sample as f64);
+            step_energy[frame / step] += weight * weighted * weighted;
+        }
+    }
+
+    let block_powers: Vec<f64> = step_energy
+        .windows(4)
+        .map(|steps| steps.iter().sum::<f64>() / block as f64)
+        .filter(|&power| power_to_lufs(power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if block_powers.is_empty() {
+        return None;
+    }
+
+    let relative_gate = power_to_lufs(mean(&block_powers)) - RELATIVE_GATE_LU;
+    let gated: Vec<f64> = block_powers.into_iter().filter(|&power| power_to_lufs(power) > relative_gate).collect();
+    (!gated.is_empty()).then(|| power_to_lufs(mean(&gated)))
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// BS.1770 weighting of `channel` out of `channels`
+fn channel_weight(channels: usize, channel: usize) -> f64 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4) | (6, 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Second-order IIR filter in transposed direct form II
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two BS.1770 K-weighting stages, with coefficients derived for any sample rate
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+
+        // +4 dB high shelf around 1.7 kHz
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        // High pass around 38 Hz
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    /// 1 kHz sine at `peak_db` dBFS in every channel, for each `(seconds, peak_db)` section in turn
+    fn sine(sample_rate: u32, channels: u16, sections: &[(f64, f64)]) -> AudioBuffer {
+        let mut samples = Vec::new();
+        let mut frame = 0usize;
+        for &(seconds, peak_db) in sections {
+            let amplitude = 10f64.powf(peak_db / 20.0);
+            for _ in 0..(seconds * sample_rate as f64) as usize {
+                let value = amplitude * (2.0 * PI * 1000.0 * frame as f64 / sample_rate as f64).sin();
+                samples.extend(std::iter::repeat_n(value as f32, channels as usize));
+                frame += 1;
+            }
+        }
+        AudioBuffer { sample_rate, channels, format: PcmFormat::Float32, samples }
+    }
+
+    #[test]
+    fn test_reference_sine_reads_its_level() {
+        // EBU Tech 3341 case 1: a stereo 1 kHz sine at -23 dBFS is -23 LUFS, at any sample rate
+        for sample_rate in [44100, 48000] {
+            let loudness = integrated_loudness(&sine(sample_rate, 2, &[(20.0, -23.0)])).unwrap();
+            assert!((loudness + 23.0).abs() < 0.1, "{} LUFS at {} Hz", loudness, sample_rate);
+        }
+        let mono = integrated_loudness(&sine(48000, 1, &[(5.0, -23.0)])).unwrap();
+        assert!((mono + 26.0).abs() < 0.1, "{} LUFS", mono);
+    }
+
+    #[test]
+    fn test_gating_ignores_silence_and_quiet_passages() {
+        // EBU Tech 3341 case 3: -36, -23 and -36 dBFS sections measure -23 LUFS
+        let loudness = integrated_loudness(&sine(48000, 2, &[(10.0, -36.0), (60.0, -23.0), (10.0, -36.0)])).unwrap();
+        assert!((loudness + 23.0).abs() < 0.1, "{} LUFS", loudness);
+
+        let with_silence = integrated_loudness(&sine(48000, 2, &[(5.0, -200.0), (30.0, -23.0), (5.0, -200.0)])).unwrap();
+        assert!((with_silence + 23.0).abs() < 0.1, "{} LUFS", with_silence);
+
+        assert_eq!(integrated_loudness(&sine(48000, 2, &[(2.0, -200.0)])), None);
+        assert_eq!(integrated_loudness(&sine(48000, 2, &[(0.3, -23.0)])), None);
+    }
+}
@@ -0,0 +1,14 @@
+//! Signal analysis and shaping used by the processors.
+//!
+//! Everything here works on an in-memory `AudioBuffer` in frames, so channels are
+//! always treated together.
+
+pub mod fade;
+pub mod level;
+pub mod limiter;
+pub mod loudness;
+pub mod onset;
+pub mod silence;
+pub mod tempo;
+pub mod true_peak;
+pub mod zero_crossing;
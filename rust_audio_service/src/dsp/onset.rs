@@ -0,0 +1,215 @@
+use std::f32::consts::PI;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::audio::AudioBuffer;
+
+/// Find the frames where new sounds start, e.g. drum hits.
+///
+/// Uses spectral flux: the summed increase in (log-compressed) magnitude across
+/// all frequency bins from one analysis window to the next. Peaks in the flux
+/// that stand out from their surroundings by a margin set by `sensitivity`
+/// (0.0 = only the strongest hits, 1.0 = nearly every peak) are onsets. Each
+/// onset is then moved to the sharpest rise in level near it, so a splice starting
+/// there begins on the attack rather than up to a window earlier or later.
+/// Onsets closer than `min_interval` frames to the previous one are dropped.
+pub fn detect_onsets(audio: &AudioBuffer, sensitivity: f64, min_interval: usize) -> Vec<usize> {
+    let mono = mix_to_mono(audio);
+    let OnsetEnvelope { values: flux, hop, window } = OnsetEnvelope::from_mono(&mono, audio.sample_rate, true, None);
+    if flux.is_empty() {
+        return Vec::new();
+    }
+
+    let delta = 0.05 + 0.45 * (1.0 - sensitivity.clamp(0.0, 1.0) as f32);
+    // Average over roughly 100 ms either side for the adaptive threshold
+    let average_span = ((audio.sample_rate as f64 * 0.1) as usize / hop).max(1);
+    let block = (audio.sample_rate as usize / 1000).max(8);
+
+    let mut onsets: Vec<usize> = Vec::new();
+    for t in 0..flux.len() {
+        let neighbours = &flux[t.saturating_sub(2)..(t + 3).min(flux.len())];
+        if neighbours.iter().any(|&f| f > flux[t]) {
+            continue;
+        }
+
+        let local = &flux[t.saturating_sub(average_span)..(t + average_span + 1).min(flux.len())];
+        let local_mean = local.iter().sum::<f32>() / local.len() as f32;
+        if flux[t] < local_mean + delta {
+            continue;
+        }
+
+        let onset = sharpest_rise(&mono, t * hop, t * hop + window, block);
+        if onsets.last().is_none_or(|&last| onset >= last + min_interval) {
+            onsets.push(onset);
+        }
+    }
+
+    onsets
+}
+
+/// Spectral flux sampled every `hop` frames and scaled so the largest value is 1.0.
+///
+/// Value `t` measures how much new energy the analysis window starting at frame
+/// `t * hop` has compared to the one before. Magnitudes are log-compressed for
+/// onset picking so quiet notes register; without compression loud hits dominate,
+/// which suits beat tracking. Empty for silent audio.
+pub struct OnsetEnvelope {
+    pub values: Vec<f32>,
+    pub hop: usize,
+    pub window: usize,
+}
+
+impl OnsetEnvelope {
+    pub fn new(audio: &AudioBuffer, compress: bool) -> Self {
+        Self::from_mono(&mix_to_mono(audio), audio.sample_rate, compress, None)
+    }
+
+    /// Uncompressed flux of frequencies below `cutoff_hz` only, e.g. to follow kick drums
+    pub fn low_band(audio: &AudioBuffer, cutoff_hz: f64) -> Self {
+        Self::from_mono(&mix_to_mono(audio), audio.sample_rate, false, Some(cutoff_hz))
+    }
+
+    fn from_mono(mono: &[f32], sample_rate: u32, compress: bool, cutoff_hz: Option<f64>) -> Self {
+        // About 23 ms windows with 75% overlap, whatever the sample rate
+        let window = ((sample_rate as f64 * 0.023) as usize).next_power_of_two().max(64);
+        let hop = window / 4;
+        let bins = cutoff_hz.map_or(window / 2 + 1, |hz| ((hz * window as f64 / sample_rate as f64).ceil() as usize).clamp(1, window / 2 + 1));
+
+        let flux = spectral_flux(mono, window, hop, bins, compress);
+        let peak = flux.iter().cloned().fold(0.0, f32::max);
+        let values = if peak > 0.0 { flux.iter().map(|&f| f / peak).collect() } else { Vec::new() };
+
+        Self { values, hop, window }
+    }
+}
+
+/// Average the channels of each frame
+fn mix_to_mono(audio: &AudioBuffer) -> Vec<f32> {
+    let channels = audio.channels.max(1) as usize;
+    audio.samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Half-wave rectified spectral flux over the lowest `bins` bins for each hop, with the signal zero-padded at the end
+fn spectral_flux(mono: &[f32], window: usize, hop: usize, bins: usize, compress: bool) -> Vec<f32> {
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(window);
+    let hann: Vec<f32> = (0..window)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window as f32).cos())
+        .collect();
+
+    let hops = mono.len().div_ceil(hop);
+    let mut previous = vec![0.0f32; bins];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); window];
+    let mut flux = Vec::with_capacity(hops);
+
+    for t in 0..hops {
+        for (i, value) in buffer.iter_mut().enumerate() {
+            let sample = mono.get(t * hop + i).copied().unwrap_or(0.0);
+            *value = Complex::new(sample * hann[i], 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let mut total = 0.0;
+        for (bin, last) in previous.iter_mut().enumerate() {
+            let magnitude = if compress { (100.0 * buffer[bin].norm()).ln_1p() } else { buffer[bin].norm() };
+            total += (magnitude - *last).max(0.0);
+            *last = magnitude;
+        }
+        flux.push(total);
+    }
+
+    flux
+}
+
+/// Start of the `block`-frame block in `start..end` whose level jumps most above the block before it
+fn sharpest_rise(mono: &[f32], start: usize, end: usize, block: usize) -> usize {
+    // Floor at -60 dB so tiny changes in near-silence don't count as a rise
+    const FLOOR: f32 = 1e-6;
+    let end = end.min(mono.len());
+    let energy = |from: usize| {
+        let samples = &mono[from..(from + block).min(mono.len())];
+        let mean_square = samples.iter().map(|&s| s * s).sum::<f32>() / samples.len().max(1) as f32;
+        (mean_square + FLOOR).ln()
+    };
+
+    let mut best = (start, f32::MIN);
+    let mut from = start;
+    while from < end {
+        let before = if from >= block { energy(from - block) } else { FLOOR.ln() };
+        let rise = energy(from) - before;
+        if rise > best.1 {
+            best = (from, rise);
+        }
+        from += block;
+    }
+
+    best.0.min(mono.len().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Low noise with a decaying 300 Hz tone starting at each of `hits` (in seconds)
+    fn hits(hits: &[f64]) -> AudioBuffer {
+        let frames = SAMPLE_RATE as usize * 3;
+        let mut noise_state = 1u32;
+        let samples = (0..frames)
+            .map(|frame| {
+                noise_state = noise_state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let mut sample = (noise_state >> 8) as f32 / (1 << 24) as f32 * 0.002 - 0.001;
+                for &hit in hits {
+                    let t = frame as f64 / SAMPLE_RATE as f64 - hit;
+                    if t >= 0.0 {
+                        sample += (0.8 * (-t / 0.05).exp() * (2.0 * std::f64::consts::PI * 300.0 * t).sin()) as f32;
+                    }
+                }
+                sample
+            })
+            .collect();
+        AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_detects_hits_close_to_their_start() {
+        let expected = [0.5, 1.2, 2.0];
+        let onsets = detect_onsets(&hits(&expected), 0.5, 400);
+
+        assert_eq!(onsets.len(), 3, "{:?}", onsets);
+        for (&onset, &hit) in onsets.iter().zip(&expected) {
+            let error = (onset as f64 / SAMPLE_RATE as f64 - hit).abs();
+            assert!(error <= 0.005, "onset at {} frames, expected {}s", onset, hit);
+        }
+    }
+
+    #[test]
+    fn test_min_interval_merges_close_hits() {
+        let audio = hits(&[0.5, 0.6, 2.0]);
+        assert_eq!(detect_onsets(&audio, 0.5, 400).len(), 3);
+
+        let onsets = detect_onsets(&audio, 0.5, 1200);
+        assert_eq!(onsets.len(), 2, "{:?}", onsets);
+        assert!(onsets[1] >= 16000 - 40);
+    }
+
+    #[test]
+    fn test_silence_has_no_onsets() {
+        let silence = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: vec![0.0; SAMPLE_RATE as usize * 2],
+        };
+        assert!(detect_onsets(&silence, 1.0, 0).is_empty());
+    }
+}
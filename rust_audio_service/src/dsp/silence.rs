@@ -0,0 +1,58 @@
+use std::ops::Range;
+
+use crate::audio::AudioBuffer;
+use super::level::rms_db;
+
+/// Frame ranges of `audio` that are not silent, in order.
+///
+/// Level is measured in blocks of `block` frames, and a block whose RMS is below
+/// `threshold_db` is silent, so range edges fall on block boundaries. Silent
+/// stretches shorter than `min_silence` frames, like the gap between two words,
+/// are kept as part of the sound around them. Leading and trailing silence is
+/// always dropped.
+pub fn sounding_ranges(audio: &AudioBuffer, threshold_db: f64, block: usize, min_silence: usize) -> Vec<Range<usize>> {
+    let block = block.max(1);
+    let total_frames = audio.frame_count();
+    let mut ranges = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+
+    for start in (0..total_frames).step_by(block) {
+        if rms_db(audio, start, block) < threshold_db {
+            continue;
+        }
+        let end = (start + block).min(total_frames);
+        current = match current {
+            Some(range) if start - range.end < min_silence => Some(range.start..end),
+            Some(range) => {
+                ranges.push(range);
+                Some(start..end)
+            },
+            None => Some(start..end),
+        };
+    }
+
+    ranges.extend(current);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    #[test]
+    fn test_short_gaps_do_not_split() {
+        // 10 frame blocks: sound, 2 silent blocks, sound, 5 silent blocks, sound, then trailing silence
+        let pattern = [1, 0, 0, 1, 0, 0, 0, 0, 0, 1, 1, 0, 0];
+        let audio = AudioBuffer {
+            sample_rate: 1000,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: pattern.iter().flat_map(|&on| vec![on as f32 * 0.5; 20]).collect(),
+        };
+
+        assert_eq!(sounding_ranges(&audio, -40.0, 10, 30), vec![0..40, 90..110]);
+        assert_eq!(sounding_ranges(&audio, -40.0, 10, 10), vec![0..10, 30..40, 90..110]);
+        assert!(sounding_ranges(&audio, 0.0, 10, 30).is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::audio::AudioBuffer;
+use super::onset::{detect_onsets, OnsetEnvelope};
+
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+/// Tempo the estimate leans towards when several are plausible, e.g. 60 vs 120 BPM
+const PREFERRED_BPM: f64 = 120.0;
+/// Autocorrelation at the beat period, relative to the envelope's variance, below which there is no steady beat
+const MIN_PERIODICITY: f64 = 0.1;
+/// Upper limit of the bass band used to follow kick drums
+const ACCENT_CUTOFF_HZ: f64 = 150.0;
+
+/// A constant-tempo grid of beats and bars over a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatGrid {
+    pub bpm: f64,
+    pub beats_per_bar: u32,
+    /// Position of the first downbeat in frames; always within the first bar of the track
+    pub first_downbeat: f64,
+    pub sample_rate: u32,
+}
+
+impl BeatGrid {
+    /// Length of one beat in frames
+    pub fn beat_frames(&self) -> f64 {
+        self.sample_rate as f64 * 60.0 / self.bpm
+    }
+
+    /// Length of one bar in frames
+    pub fn bar_frames(&self) -> f64 {
+        self.beat_frames() * self.beats_per_bar as f64
+    }
+
+    /// Number of frames in `beats` beats
+    pub fn frames_for_beats(&self, beats: u32) -> usize {
+        (self.beat_frames() * beats as f64).round() as usize
+    }
+
+    /// Frame of downbeat `bar`, counting from the first
+    pub fn downbeat(&self, bar: usize) -> usize {
+        (self.first_downbeat + bar as f64 * self.bar_frames()).round() as usize
+    }
+
+    /// Index of the first downbeat at or after `frame`
+    pub fn first_bar_from(&self, frame: usize) -> usize {
+        ((frame as f64 - self.first_downbeat) / self.bar_frames()).ceil().max(0.0) as usize
+    }
+}
+
+/// Estimate the tempo, beat phase and downbeats of `audio`.
+///
+/// The tempo comes from autocorrelating the log-compressed onset envelope plus
+/// a bass-only one, so kicks and snares count alike, weighted towards 120 BPM to
+/// settle half/double-time ambiguity; pass `bpm` to skip this and use a known tempo. Beats are then fitted to the detected onsets by least squares,
+/// which refines both tempo and phase to the frame. The downbeat is taken to be
+/// the beat position in the bar with the most low-frequency accent, since kicks
+/// tend to land on it while snares land between. Returns `None` if there is no
+/// rhythmic content to lock on to.
+pub fn detect_beat_grid(audio: &AudioBuffer, beats_per_bar: u32, bpm: Option<f64>) -> Option<BeatGrid> {
+    let envelope = OnsetEnvelope::new(audio, false);
+    if envelope.values.is_empty() {
+        return None;
+    }
+    let envelope_rate = audio.sample_rate as f64 / envelope.hop as f64;
+
+    let bass = OnsetEnvelope::low_band(audio, ACCENT_CUTOFF_HZ);
+
+    let period = match bpm {
+        Some(bpm) => 60.0 * envelope_rate / bpm,
+        None => {
+            // Each envelope peaks at 1.0, so a quiet kick counts as much as a loud broadband snare
+            let compressed = OnsetEnvelope::new(audio, true);
+            let combined: Vec<f32> = compressed.values
+                .iter()
+                .zip(bass.values.iter().chain(std::iter::repeat(&0.0)))
+                .map(|(full, low)| full + low)
+                .collect();
+            estimate_period(&combined, envelope_rate)?
+        },
+    };
+    let phase = estimate_phase(&envelope.values, period);
+
+    // Beat positions in frames; the envelope peaks about half a window after an attack begins
+    let mut beat = period * envelope.hop as f64;
+    let mut origin = phase * envelope.hop as f64 + (envelope.window / 2) as f64 - envelope.hop as f64;
+
+    let onsets = detect_onsets(audio, 0.7, 0);
+    for _ in 0..2 {
+        (origin, beat) = fit_to_onsets(&onsets, origin, beat, bpm.is_none());
+    }
+
+    // Accent of each beat position in the bar, taking the strongest bass flux near each beat
+    let beats_per_bar = beats_per_bar.max(1);
+    let mut accents = vec![0.0f32; beats_per_bar as usize];
+    let mut k = 0usize;
+    loop {
+        let frame = origin + k as f64 * beat;
+        // Inverse of the window offset applied to `origin` above, give or take two hops
+        let centre = ((frame - (bass.window / 2) as f64) / bass.hop as f64).round() as isize + 1;
+        if centre >= bass.values.len() as isize {
+            break;
+        }
+        let near = &bass.values[(centre - 2).max(0) as usize..(centre + 3).clamp(0, bass.values.len() as isize) as usize];
+        let value = near.iter().cloned().fold(0.0, f32::max);
+        accents[k % beats_per_bar as usize] += value;
+        k += 1;
+    }
+    let downbeat_offset = accents
+        .iter()
+        .enumerate()
+        .fold(0, |best, (i, &accent)| if accent > accents[best] { i } else { best });
+
+    let bar = beat * beats_per_bar as f64;
+    let first_downbeat = (origin + downbeat_offset as f64 * beat).rem_euclid(bar);
+
+    Some(BeatGrid {
+        bpm: 60.0 * audio.sample_rate as f64 / beat,
+        beats_per_bar,
+        first_downbeat,
+        sample_rate: audio.sample_rate,
+    })
+}
+
+/// Beat period in envelope samples, from the strongest autocorrelation peak in the allowed tempo range.
+///
+/// `None` if even that peak is too weak for the envelope to have a steady beat.
+fn estimate_period(envelope: &[f32], envelope_rate: f64) -> Option<f64> {
+    let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+    let centred: Vec<f64> = envelope.iter().map(|&e| (e - mean) as f64).collect();
+
+    let min_lag = (60.0 * envelope_rate / MAX_BPM).floor().max(1.0) as usize;
+    let max_lag = ((60.0 * envelope_rate / MIN_BPM).ceil() as usize).min(centred.len() / 2);
+    if max_lag <= min_lag + 1 {
+        return None;
+    }
+
+    let autocorrelation = |lag: usize| {
+        let sum: f64 = centred.iter().zip(&centred[lag..]).map(|(a, b)| a * b).sum();
+        sum / (centred.len() - lag) as f64
+    };
+    let scores: Vec<f64> = (min_lag - 1..=max_lag + 1).map(autocorrelation).collect();
+
+    let weighted = |i: usize| {
+        let bpm = 60.0 * envelope_rate / (min_lag - 1 + i) as f64;
+        scores[i] * (-0.5 * (bpm / PREFERRED_BPM).log2().powi(2)).exp()
+    };
+    let best = (1..scores.len() - 1).max_by(|&a, &b| weighted(a).total_cmp(&weighted(b)))?;
+    if scores[best] <= MIN_PERIODICITY * autocorrelation(0) {
+        return None;
+    }
+
+    // Parabolic interpolation between neighbouring lags for a fractional period
+    let (left, centre, right) = (scores[best - 1], scores[best], scores[best + 1]);
+    let curvature = left - 2.0 * centre + right;
+    let shift = if curvature < 0.0 { (0.5 * (left - right) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+
+    Some((min_lag - 1 + best) as f64 + shift)
+}
+
+/// Offset (in envelope samples, less than one period) whose beat train collects the most onset energy
+fn estimate_phase(envelope: &[f32], period: f64) -> f64 {
+    let score = |phase: usize| {
+        let mut total = 0.0;
+        let mut position = phase as f64;
+        while let Some(&value) = envelope.get(position.round() as usize) {
+            total += value;
+            position += period;
+        }
+        total
+    };
+
+    (0..period.ceil() as usize)
+        .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+        .unwrap_or(0) as f64
+}
+
+/// Least-squares fit of `origin + k * beat` to the onsets within an eighth of a beat of the grid.
+///
+/// Only the origin moves when `fit_tempo` is false. Returns the inputs unchanged
+/// if too few onsets line up.
+fn fit_to_onsets(onsets: &[usize], origin: f64, beat: f64, fit_tempo: bool) -> (f64, f64) {
+    let points: Vec<(f64, f64)> = onsets
+        .iter()
+        .filter_map(|&onset| {
+            let k = ((onset as f64 - origin) / beat).round();
+            let error = onset as f64 - (origin + k * beat);
+            (error.abs() <= beat / 8.0).then_some((k, onset as f64))
+        })
+        .collect();
+
+    if points.len() < 2 {
+        return (origin, beat);
+    }
+
+    let n = points.len() as f64;
+    let mean_k = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_frame = points.iter().map(|p| p.1).sum::<f64>() / n;
+
+    let beat = if fit_tempo {
+        let covariance: f64 = points.iter().map(|(k, frame)| (k - mean_k) * (frame - mean_frame)).sum();
+        let variance: f64 = points.iter().map(|(k, _)| (k - mean_k).powi(2)).sum();
+        if variance > 0.0 { covariance / variance } else { beat }
+    } else {
+        beat
+    };
+
+    (mean_frame - beat * mean_k, beat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    const SAMPLE_RATE: u32 = 22050;
+
+    /// Eight bars of 4/4 at `bpm` starting at `start` seconds: a kick on beat 1, a
+    /// softer one on 3, a louder noise snare on 2 and 4 and a quiet hat on the off-beats
+    fn drum_loop(bpm: f64, start: f64) -> AudioBuffer {
+        let beat = 60.0 / bpm;
+        let frames = ((start + beat * 33.0) * SAMPLE_RATE as f64) as usize;
+        let mut samples = vec![0.0f32; frames];
+        let mut noise_state = 1u32;
+
+        for k in 0..64 {
+            let at = start + k as f64 * beat / 2.0;
+            let (level, tone, decay) = match k % 8 {
+                0 => (0.8, 60.0, 0.12),
+                4 => (0.6, 60.0, 0.1),
+                2 | 6 => (0.9, 0.0, 0.06),
+                _ => (0.15, 3000.0, 0.01),
+            };
+            let first = (at * SAMPLE_RATE as f64) as usize;
+            for (i, sample) in samples[first..].iter_mut().take(SAMPLE_RATE as usize / 4).enumerate() {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                // A tone of 0 Hz stands for noise
+                let wave = if tone > 0.0 {
+                    (std::f64::consts::TAU * tone * t).sin()
+                } else {
+                    noise_state = noise_state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    (noise_state >> 8) as f64 / (1 << 23) as f64 - 1.0
+                };
+                *sample += (level * (-t / decay).exp() * wave) as f32;
+            }
+        }
+
+        AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_detects_tempo_and_downbeat() {
+        for (bpm, start) in [(128.0, 0.3), (95.0, 1.1)] {
+            let grid = detect_beat_grid(&drum_loop(bpm, start), 4, None).unwrap();
+            assert!((grid.bpm - bpm).abs() < 0.5, "detected {} BPM, expected {}", grid.bpm, bpm);
+
+            let bar_seconds = grid.bar_frames() / SAMPLE_RATE as f64;
+            let expected = start % bar_seconds;
+            let detected = grid.first_downbeat / SAMPLE_RATE as f64;
+            assert!((detected - expected).abs() < 0.01, "downbeat at {}s, expected {}s", detected, expected);
+        }
+    }
+
+    #[test]
+    fn test_known_tempo_only_fits_phase() {
+        let grid = detect_beat_grid(&drum_loop(128.0, 0.3), 4, Some(128.0)).unwrap();
+        assert_eq!(grid.bpm, 128.0);
+        assert!((grid.first_downbeat / SAMPLE_RATE as f64 - 0.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_grid_positions() {
+        let grid = BeatGrid { bpm: 120.0, beats_per_bar: 4, first_downbeat: 100.0, sample_rate: 1000 };
+        assert_eq!(grid.frames_for_beats(3), 1500);
+        assert_eq!(grid.downbeat(2), 4100);
+        assert_eq!(grid.first_bar_from(0), 0);
+        assert_eq!(grid.first_bar_from(101), 1);
+        assert_eq!(grid.first_bar_from(2100), 1);
+    }
+}
@@ -0,0 +1,115 @@
+use std::f64::consts::PI;
+
+use crate::audio::AudioBuffer;
+use super::level::amplitude_to_db;
+
+/// Samples either side of the gap being interpolated; 12 taps per phase as in BS.1770-4 Annex 2
+const HALF_TAPS: usize = 6;
+
+/// Oversampling factor that brings `sample_rate` up to at least 176.4 kHz
+fn oversampling(sample_rate: u32) -> usize {
+    match sample_rate {
+        0..=95_999 => 4,
+        96_000..=191_999 => 2,
+        _ => 1,
+    }
+}
+
+/// Windowed-sinc interpolation filter, one set of taps per point between two samples.
+///
+/// `phases[p - 1]` reconstructs the signal `p / factor` of the way from sample `n`
+/// to sample `n + 1`, from samples `n + 1 - HALF_TAPS ..= n + HALF_TAPS`.
+struct Interpolator {
+    phases: Vec<[f64; 2 * HALF_TAPS]>,
+}
+
+impl Interpolator {
+    fn new(factor: usize) -> Self {
+        let phases = (1..factor)
+            .map(|p| {
+                let offset = p as f64 / factor as f64;
+                let mut taps = [0.0; 2 * HALF_TAPS];
+                for (k, tap) in taps.iter_mut().enumerate() {
+                    // Distance from the point being reconstructed to the sample this tap weights
+                    let t = offset - (k as f64 + 1.0 - HALF_TAPS as f64);
+                    let window = 0.5 + 0.5 * (PI * t / HALF_TAPS as f64).cos();
+                    *tap = window * (PI * t).sin() / (PI * t);
+                }
+                // Unity gain at DC so a constant signal reads its own level
+                let sum: f64 = taps.iter().sum();
+                taps.map(|tap| tap / sum)
+            })
+            .collect();
+        Self { phases }
+    }
+}
+
+/// Highest absolute level reached by each frame or on the way to the next one, over all channels.
+///
+/// The signal is oversampled 4x (2x from 96 kHz, not at all from 192 kHz) so peaks
+/// that fall between samples, which a DAC or a lossy encoder will reconstruct, are
+/// seen. Audio before the start and after the end is taken as silence.
+pub fn peak_envelope(audio: &AudioBuffer) -> Vec<f64> {
+    let channels = audio.channels.max(1) as usize;
+    let frames = audio.frame_count();
+    let interpolator = Interpolator::new(oversampling(audio.sample_rate));
+    let mut envelope = vec![0.0f64; frames];
+
+    for channel in 0..channels {
+        let sample = |frame: isize| -> f64 {
+            if frame < 0 || frame as usize >= frames {
+                0.0
+            } else {
+                audio.samples[frame as usize * channels + channel] as f64
+            }
+        };
+
+        for (frame, peak) in envelope.iter_mut().enumerate() {
+            let first = frame as isize + 1 - HALF_TAPS as isize;
+            let mut level = sample(frame as isize).abs();
+            for taps in &interpolator.phases {
+                let value: f64 = taps.iter().enumerate().map(|(k, tap)| tap * sample(first + k as isize)).sum();
+                level = level.max(value.abs());
+            }
+            *peak = peak.max(level);
+        }
+    }
+
+    envelope
+}
+
+/// True peak of `audio` as a linear amplitude (1.0 = full scale)
+pub fn true_peak(audio: &AudioBuffer) -> f64 {
+    peak_envelope(audio).into_iter().fold(0.0, f64::max)
+}
+
+/// True peak of `audio` in dBTP; digital silence is `f64::NEG_INFINITY`
+pub fn true_peak_db(audio: &AudioBuffer) -> f64 {
+    amplitude_to_db(true_peak(audio))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    #[test]
+    fn test_finds_peaks_between_samples() {
+        // A quarter-rate sine sampled 45 degrees off its crests never has a sample above 0.707
+        let sample_rate = 44100;
+        let audio = AudioBuffer {
+            sample_rate,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..4410).map(|n| (PI / 2.0 * n as f64 + PI / 4.0).sin() as f32).collect(),
+        };
+        let sample_peak = audio.samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(sample_peak < 0.71);
+
+        let peak_db = true_peak_db(&audio);
+        assert!(peak_db.abs() < 0.2, "{} dBTP", peak_db);
+
+        let silence = AudioBuffer { samples: vec![0.0; 100], ..audio };
+        assert_eq!(true_peak_db(&silence), f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::audio::AudioBuffer;
+
+/// Sum of all channels in `frame`, used to judge the frame as a whole
+fn frame_sum(audio: &AudioBuffer, frame: usize) -> f32 {
+    let channels = audio.channels as usize;
+    audio.samples[frame * channels..(frame + 1) * channels].iter().sum()
+}
+
+/// Whether cutting at `boundary` (between frames `boundary - 1` and `boundary`)
+/// lands on a zero crossing
+fn is_zero_crossing(audio: &AudioBuffer, boundary: usize) -> bool {
+    let frames = audio.frame_count();
+    if frames == 0 {
+        return false;
+    }
+    if boundary == 0 {
+        return frame_sum(audio, 0) == 0.0;
+    }
+    if boundary >= frames {
+        return frame_sum(audio, frames - 1) == 0.0;
+    }
+
+    let before = frame_sum(audio, boundary - 1);
+    let after = frame_sum(audio, boundary);
+    after == 0.0 || (before < 0.0) != (after < 0.0)
+}
+
+/// Find the zero crossing nearest to the frame boundary `boundary`.
+///
+/// A crossing is where the sum of the channels changes sign, so every channel is
+/// cut at the same frame. Only boundaries within `radius` frames of `boundary`
+/// and inside `min..=max` are considered; ties go to the earlier boundary.
+/// Returns `None` if there is no crossing in range.
+pub fn nearest_zero_crossing(audio: &AudioBuffer, boundary: usize, radius: usize, min: usize, max: usize) -> Option<usize> {
+    let max = max.min(audio.frame_count());
+    let in_range = |candidate: usize| candidate >= min && candidate <= max && is_zero_crossing(audio, candidate);
+
+    (0..=radius).find_map(|distance| {
+        let before = boundary.checked_sub(distance).filter(|&candidate| in_range(candidate));
+        before.or_else(|| Some(boundary + distance).filter(|&candidate| in_range(candidate)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+
+    fn stereo(frames: &[(f32, f32)]) -> AudioBuffer {
+        AudioBuffer {
+            sample_rate: 100,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: frames.iter().flat_map(|&(left, right)| [left, right]).collect(),
+        }
+    }
+
+    #[test]
+    fn test_finds_nearest_crossing() {
+        let audio = stereo(&[(0.5, 0.5), (0.4, 0.4), (0.2, 0.1), (-0.1, -0.2), (-0.3, -0.3), (-0.1, 0.3), (0.4, 0.4)]);
+        assert_eq!(nearest_zero_crossing(&audio, 1, 3, 0, 7), Some(3));
+        assert_eq!(nearest_zero_crossing(&audio, 6, 3, 0, 7), Some(5));
+        assert_eq!(nearest_zero_crossing(&audio, 1, 1, 0, 7), None);
+    }
+
+    #[test]
+    fn test_prefers_earlier_and_respects_bounds() {
+        let audio = stereo(&[(0.5, 0.5), (-0.5, -0.5), (0.5, 0.5), (0.5, 0.5), (-0.5, -0.5)]);
+        assert_eq!(nearest_zero_crossing(&audio, 3, 2, 0, 5), Some(2));
+        assert_eq!(nearest_zero_crossing(&audio, 3, 2, 3, 5), Some(4));
+        assert_eq!(nearest_zero_crossing(&audio, 3, 2, 3, 3), None);
+    }
+}
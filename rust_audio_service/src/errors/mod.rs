@@ -1,7 +1,7 @@
 use std::fmt;
 use std::io;
 use actix_web::{HttpResponse, ResponseError};
-use actix_web::http::StatusCode;
+use actix_web::http::{header, StatusCode};
 use serde_json::json;
 
 #[derive(Debug)]
@@ -10,9 +10,14 @@ pub enum AudioError {
     WavError(hound::Error),
     InvalidDuration(String),
     InvalidSpliceCount(String),
+    SpliceTooLong { requested: f64, available: f64 },
+    TooQuiet { threshold_db: f64, attempts: u32 },
+    Busy { retry_after_secs: u64 },
+    TooLarge(String),
     ProcessingError(String),
     FileNotFound(String),
-    InvalidFormat(String)
+    InvalidFormat(String),
+    DecodeError(String)
 }
 
 pub type AudioResult<T> = Result<T, AudioError>;
@@ -24,9 +29,20 @@ impl fmt::Display for AudioError{
             AudioError::WavError(e) => write!(f, "Wav Error: {}", e),
             AudioError::InvalidDuration(msg) => write!(f, "Invalid duration: {}", msg),
             AudioError::InvalidSpliceCount(msg) => write!(f, "Invalid splice count: {}", msg),
+            AudioError::SpliceTooLong { requested, available } => write!(
+                f, "Splice too long: requested {:.3}s but only {:.3}s of audio is available", requested, available
+            ),
+            AudioError::TooQuiet { threshold_db, attempts } => write!(
+                f, "Too quiet: no splice above {:.1} dBFS RMS found after {} attempts, the track may be mostly silent", threshold_db, attempts
+            ),
+            AudioError::Busy { retry_after_secs } => write!(
+                f, "Server busy: too much audio is being processed, retry in {} seconds", retry_after_secs
+            ),
+            AudioError::TooLarge(msg) => write!(f, "Too large: {}", msg),
             AudioError::ProcessingError(msg) => write!(f, "Processing error: {}", msg),
             AudioError::FileNotFound(path) => write!(f, "File not found: {}", path),
-            AudioError::InvalidFormat(msg) => write!(f, "Invalid format: {}", msg)
+            AudioError::InvalidFormat(msg) => write!(f, "Invalid format: {}", msg),
+            AudioError::DecodeError(msg) => write!(f, "Decode error: {}", msg)
         }
     }
 }
@@ -35,19 +51,22 @@ impl std::error::Error for AudioError {}
 
 impl ResponseError for AudioError {
     fn error_response(&self) -> HttpResponse {
-        let status = match self {
-            AudioError::InvalidDuration(_) | AudioError::InvalidSpliceCount(_) => StatusCode::BAD_REQUEST,
-            AudioError::FileNotFound(_) => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
-
-        HttpResponse::build(status).json(json!({"error": self.to_string(), "error_type": format!("{:?}", self)}))
+        let mut response = HttpResponse::build(self.status_code());
+        if let AudioError::Busy { retry_after_secs } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        response.json(json!({"error": self.to_string(), "error_type": format!("{:?}", self)}))
     }
 
     fn status_code(&self) -> StatusCode {
         match self {
             AudioError::InvalidDuration(_) | AudioError::InvalidSpliceCount(_) => StatusCode::BAD_REQUEST,
+            AudioError::SpliceTooLong { .. } | AudioError::TooQuiet { .. } => StatusCode::BAD_REQUEST,
+            AudioError::DecodeError(_) | AudioError::ProcessingError(_) => StatusCode::BAD_REQUEST,
+            AudioError::InvalidFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             AudioError::FileNotFound(_) => StatusCode::NOT_FOUND,
+            AudioError::Busy { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AudioError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -63,4 +82,13 @@ impl From<hound::Error> for AudioError {
     fn from(err: hound::Error) -> AudioError {
         AudioError::WavError(err)
     }
-}
\ No newline at end of file
+}
+impl From<symphonia::core::errors::Error> for AudioError {
+    fn from(err: symphonia::core::errors::Error) -> AudioError {
+        match err {
+            symphonia::core::errors::Error::IoError(e) => AudioError::IoError(e),
+            symphonia::core::errors::Error::Unsupported(msg) => AudioError::InvalidFormat(format!("Unsupported audio: {}", msg)),
+            e => AudioError::DecodeError(e.to_string()),
+        }
+    }
+}
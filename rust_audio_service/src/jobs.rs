@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, error};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::audio::DecodeOptions;
+use crate::errors::{AudioError, AudioResult};
+use crate::processors::{processor_for, ProcessorConfig, ProcessingMetadata};
+use crate::utils::create_zip_from_result;
+use crate::workspace::Workspace;
+
+/// How long a finished job and its ZIP are kept before they are removed
+const JOB_TTL_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A processing request run in the background, polled for by its id
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub status: JobStatus,
+    pub processor_type: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ProcessingMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    config: ProcessorConfig,
+    // Shared with the worker; the upload, output and ZIP go when the last copy is dropped
+    #[serde(skip)]
+    workspace: Arc<Workspace>,
+}
+
+impl Job {
+    /// Where the result ZIP is, once the job has succeeded
+    pub fn zip_path(&self) -> Option<PathBuf> {
+        (self.status == JobStatus::Succeeded).then(|| self.workspace.zip_path())
+    }
+}
+
+/// Jobs known to the service, shared between the API handlers and the workers
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl JobStore {
+    pub fn new() -> Self {
+        Self { jobs: Mutex::new(HashMap::new()) }
+    }
+
+    /// The jobs, after dropping finished ones older than the TTL along with their workspaces
+    fn live_jobs(&self) -> MutexGuard<'_, HashMap<String, Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let expiry = Utc::now() - Duration::minutes(JOB_TTL_MINUTES);
+        jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished > expiry));
+        jobs
+    }
+
+    /// Queue `config` to run on the upload in `workspace`, returning the new job's id
+    pub fn submit(&self, workspace: Workspace, config: ProcessorConfig) -> AudioResult<String> {
+        let id = Uuid::new_v4().to_string();
+        let job = Job {
+            id: id.clone(),
+            status: JobStatus::Queued,
+            processor_type: processor_for(&config)?.processor_type().to_string(),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            metadata: None,
+            error: None,
+            config,
+            workspace: Arc::new(workspace),
+        };
+
+        self.live_jobs().insert(id.clone(), job);
+        Ok(id)
+    }
+
+    /// Snapshot of the job with `id`, if there is one and it hasn't expired
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.live_jobs().get(id).cloned()
+    }
+
+    /// Record that the job with `id` failed outside of processing, e.g. its worker could not start.
+    ///
+    /// Jobs that already finished keep their outcome.
+    pub fn fail(&self, id: &str, e: &AudioError) {
+        self.update(id, |job| {
+            if job.finished_at.is_none() {
+                job.status = JobStatus::Failed;
+                job.finished_at = Some(Utc::now());
+                job.error = Some(e.to_string());
+            }
+        });
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(id) {
+            change(job);
+        }
+    }
+
+    /// Process the job with `id` and zip its output, recording how it went.
+    ///
+    /// Decoding is limited by `options`. This blocks for as long as processing takes,
+    /// so it belongs on a blocking thread.
+    pub fn run(&self, id: &str, options: DecodeOptions) {
+        let Some(job) = self.get(id) else {
+            return;
+        };
+        self.update(id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(Utc::now());
+        });
+        info!("Running job {} ({})", id, job.processor_type);
+
+        let workspace = &job.workspace;
+        let outcome = processor_for(&job.config)
+            .and_then(|processor| processor.process(&workspace.input_path(), workspace, &job.config, options))
+            .and_then(|result| {
+                create_zip_from_result(&result, &workspace.zip_path())?;
+                Ok(result.metadata)
+            });
+
+        self.update(id, |job| {
+            job.finished_at = Some(Utc::now());
+            match outcome {
+                Ok(metadata) => {
+                    job.status = JobStatus::Succeeded;
+                    job.metadata = Some(metadata);
+                },
+                Err(e) => {
+                    error!("Job {} failed: {}", job.id, e);
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                },
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioBuffer, PcmFormat};
+    use crate::processors::SpliceConfig;
+
+    #[test]
+    fn test_jobs_report_their_outcome() {
+        let store = JobStore::new();
+        let workspace = Workspace::create().unwrap();
+        let audio = AudioBuffer {
+            sample_rate: 8000,
+            channels: 1,
+            format: PcmFormat::Int16,
+            samples: (0..8000).map(|n| (n as f32 * 0.05).sin() * 0.5).collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Int16).unwrap();
+
+        let config = ProcessorConfig::Splice(SpliceConfig { seed: Some(3), ..SpliceConfig::new(0.25, 2) });
+        let id = store.submit(workspace, config).unwrap();
+        let job = store.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.zip_path(), None);
+
+        store.run(&id, DecodeOptions::default());
+        let job = store.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.metadata.as_ref().and_then(|metadata| metadata.seed), Some(3));
+        assert!(job.zip_path().unwrap().exists());
+
+        // Nothing was uploaded to this workspace
+        let id = store.submit(Workspace::create().unwrap(), ProcessorConfig::Reverse).unwrap();
+        store.run(&id, DecodeOptions::default());
+        let job = store.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.is_some());
+        assert!(store.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_jobs_that_never_start_fail_and_expire() {
+        let store = JobStore::new();
+        let id = store.submit(Workspace::create().unwrap(), ProcessorConfig::Reverse).unwrap();
+
+        store.fail(&id, &AudioError::ProcessingError("worker did not start".to_string()));
+        let job = store.get(&id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.finished_at.is_some());
+        assert_eq!(job.error.as_deref(), Some("Processing error: worker did not start"));
+
+        // Expired jobs are gone on the next lookup, not only on the next submit
+        store.update(&id, |job| job.finished_at = Some(Utc::now() - Duration::minutes(JOB_TTL_MINUTES + 1)));
+        assert!(store.get(&id).is_none());
+    }
+}
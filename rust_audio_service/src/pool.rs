@@ -0,0 +1,139 @@
+use std::io;
+use std::sync::Arc;
+use log::info;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::errors::{AudioError, AudioResult};
+
+/// Seconds a client is told to wait before retrying when the pool is full
+const RETRY_AFTER_SECS: u64 = 5;
+
+/// Runs CPU-bound processing on blocking threads, a limited number at a time.
+///
+/// Async handlers hand their work to the pool instead of running it inline, so a
+/// long normalize never stalls the actix worker it arrived on. At most
+/// `concurrency` tasks run at once and up to `queue_limit` more wait for a turn;
+/// beyond that new work is turned away with `AudioError::Busy`.
+pub struct ProcessingPool {
+    running: Arc<Semaphore>,
+    admitted: Arc<Semaphore>,
+}
+
+/// A place in the pool's queue, held from admission until the task finishes
+pub struct PoolSlot {
+    running: Arc<Semaphore>,
+    admitted: OwnedSemaphorePermit,
+}
+
+impl ProcessingPool {
+    pub fn new(concurrency: usize, queue_limit: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            running: Arc::new(Semaphore::new(concurrency)),
+            admitted: Arc::new(Semaphore::new(concurrency + queue_limit)),
+        }
+    }
+
+    /// Pool sized from `PROCESSING_CONCURRENCY` and `PROCESSING_QUEUE_LIMIT`.
+    ///
+    /// Concurrency defaults to the number of CPUs, and the queue to twice that.
+    pub fn from_env() -> Self {
+        let env_usize = |name: &str| std::env::var(name).ok().and_then(|value| value.parse::<usize>().ok());
+        let concurrency = env_usize("PROCESSING_CONCURRENCY")
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let queue_limit = env_usize("PROCESSING_QUEUE_LIMIT").unwrap_or(2 * concurrency);
+
+        info!("Processing pool - Concurrency: {}, Queue limit: {}", concurrency, queue_limit);
+        Self::new(concurrency, queue_limit)
+    }
+
+    /// Take a place in the queue now, or fail with `AudioError::Busy` if it is full
+    pub fn reserve(&self) -> AudioResult<PoolSlot> {
+        let admitted = self.admitted.clone().try_acquire_owned()
+            .map_err(|_| AudioError::Busy { retry_after_secs: RETRY_AFTER_SECS })?;
+        Ok(PoolSlot { running: self.running.clone(), admitted })
+    }
+
+    /// Run `task` on a blocking thread once there is room, returning its output
+    pub async fn run<T, F>(&self, task: F) -> AudioResult<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.reserve()?.run(task).await
+    }
+}
+
+impl PoolSlot {
+    /// Wait for a free worker, then run `task` on a blocking thread.
+    ///
+    /// The permits move into the blocking task, so a client that disconnects and
+    /// drops this future does not free its place while the task is still running.
+    pub async fn run<T, F>(self, task: F) -> AudioResult<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let PoolSlot { running, admitted } = self;
+        // Failures here are the server's, not the request's, so they surface as a 500
+        let running = running.acquire_owned().await
+            .map_err(|e| AudioError::IoError(io::Error::other(e)))?;
+        actix_web::rt::task::spawn_blocking(move || {
+            let _permits = (running, admitted);
+            task()
+        })
+        .await
+            .map_err(|e| AudioError::IoError(io::Error::other(format!("Processing task failed: {}", e))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_full_pool_turns_work_away() {
+        let pool = ProcessingPool::new(1, 1);
+        let first = pool.reserve().unwrap();
+        let second = pool.reserve().unwrap();
+        assert!(matches!(pool.reserve(), Err(AudioError::Busy { .. })));
+
+        assert_eq!(first.run(|| 2 + 2).await.unwrap(), 4);
+        // The finished task's place is free again
+        assert_eq!(pool.run(|| "done").await.unwrap(), "done");
+        drop(second);
+    }
+
+    #[actix_web::test]
+    async fn test_dropped_request_keeps_its_place_until_the_task_ends() {
+        let pool = Arc::new(ProcessingPool::new(1, 0));
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+
+        let request = actix_web::rt::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run(move || {
+                    started_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                }).await
+            }
+        });
+        started_rx.await.unwrap();
+
+        // The client goes away while its task is still running
+        request.abort();
+        assert!(request.await.unwrap_err().is_cancelled());
+        assert!(matches!(pool.reserve(), Err(AudioError::Busy { .. })));
+        assert_eq!(pool.running.available_permits(), 0);
+
+        release_tx.send(()).unwrap();
+        for _ in 0..100 {
+            if pool.running.available_permits() == 1 {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(pool.reserve().is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Instant;
+
+use crate::audio::AudioBuffer;
+use crate::errors::{AudioError, AudioResult};
+use super::{AudioProcessor, ProcessorConfig, ProcessedAudio, ProcessingMetadata};
+
+/// Plays the whole buffer backwards, keeping each frame's channels in order
+pub struct ReverseProcessor;
+
+impl ReverseProcessor {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AudioProcessor for ReverseProcessor {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
+        let start_time = Instant::now();
+        self.validate_config(config)?;
+
+        let mut reversed = audio.clone();
+        reversed.reverse_frames();
+
+        let mut metadata = ProcessingMetadata::new(self.processor_type(), audio);
+        metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok(ProcessedAudio {
+            clips: vec![("reversed_audio.wav".to_string(), reversed)],
+            metadata,
+        })
+    }
+
+    fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
+        match config {
+            ProcessorConfig::Reverse => Ok(()),
+            _ => Err(AudioError::ProcessingError("Invalid config for ReverseProcessor".to_string())),
+        }
+    }
+
+    fn processor_type(&self) -> &'static str {
+        "reverse"
+    }
+}
+
+/// Fades the ends of the whole buffer in and out
+pub struct FadeProcessor;
+
+impl FadeProcessor {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AudioProcessor for FadeProcessor {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
+        let start_time = Instant::now();
+        self.validate_config(config)?;
+        let ProcessorConfig::Fade(fade) = config else {
+            return Err(AudioError::ProcessingError("Invalid config for FadeProcessor".to_string()));
+        };
+
+        let mut faded = audio.clone();
+        fade.apply(&mut faded);
+
+        let mut metadata = ProcessingMetadata::new(self.processor_type(), audio);
+        metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok(ProcessedAudio {
+            clips: vec![("faded_audio.wav".to_string(), faded)],
+            metadata,
+        })
+    }
+
+    fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
+        match config {
+            ProcessorConfig::Fade(fade) => fade.validate(),
+            _ => Err(AudioError::ProcessingError("Invalid config for FadeProcessor".to_string())),
+        }
+    }
+
+    fn processor_type(&self) -> &'static str {
+        "fade"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::PcmFormat;
+    use crate::dsp::fade::Fade;
+
+    #[test]
+    fn test_buffers_are_processed_in_memory() {
+        let audio = AudioBuffer {
+            sample_rate: 1000,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: vec![0.1, -0.1, 0.2, -0.2, 0.3, -0.3, 0.4, -0.4],
+        };
+
+        let reversed = ReverseProcessor::new().process_buffer(&audio, &ProcessorConfig::Reverse).unwrap();
+        assert_eq!(reversed.clips[0].1.samples, vec![0.4, -0.4, 0.3, -0.3, 0.2, -0.2, 0.1, -0.1]);
+        assert_eq!(reversed.metadata.processor_type, "reverse");
+        assert_eq!(reversed.metadata.input_duration, 0.004);
+
+        let fade = ProcessorConfig::Fade(Fade { out_ms: 2.0, ..Default::default() });
+        let faded = FadeProcessor::new().process_buffer(&audio, &fade).unwrap();
+        assert_eq!(faded.clips[0].1.samples, vec![0.1, -0.1, 0.2, -0.2, 0.15, -0.15, 0.0, -0.0]);
+
+        assert!(FadeProcessor::new().process_buffer(&audio, &ProcessorConfig::Reverse).is_err());
+    }
+}
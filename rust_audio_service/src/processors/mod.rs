@@ -1,41 +1,347 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use crate::errors::AudioResult;
+use crate::audio::{AudioBuffer, AudioSource, DecodeOptions, PcmFormat};
+use crate::dsp::fade::Fade;
+use crate::dsp::limiter::Limiter;
+use crate::errors::{AudioError, AudioResult};
+use crate::workspace::Workspace;
 
 pub mod splice;
 pub mod normalize;
+pub mod placement;
+pub mod regions;
+pub mod silence;
+pub mod pipeline;
+pub mod effects;
+pub mod registry;
+
+pub use placement::Placement;
+pub use regions::Region;
+pub use silence::SilenceConfig;
+
+/// Beats per bar when `beats_per_bar` is not given
+const DEFAULT_BEATS_PER_BAR: u32 = 4;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProcessorConfig {
-    Splice {
-        duration: f64,
-        count: i32,
-        reverse: bool,
+    Splice(SpliceConfig),
+    /// Cut exact time ranges instead of random splices; handled by `SpliceProcessor`
+    Regions {
+        regions: Vec<Region>,
+        #[serde(default)]
+        output_format: Option<PcmFormat>,  // Defaults to the input's sample format
+        #[serde(default)]
+        fade: Option<Fade>,
     },
     Normalize {
         target_level: f64,  // Target peak level (0.0 to 1.0, where 1.0 = 0dB)
-        apply_to_splices: bool,  // If true, normalize each splice individually
+        #[serde(default)]
+        output_format: Option<PcmFormat>,  // Defaults to the input's sample format; splices are written in it too
+        #[serde(default)]
+        target_lufs: Option<f64>,  // Normalize integrated loudness to this instead; target_level then caps the peak
+        #[serde(default)]
+        true_peak: bool,  // Measure peaks 4x oversampled, catching overs between samples
+        #[serde(default)]
+        limiter: Option<Limiter>,  // Lookahead limiter run after the gain; target_lufs is then no longer capped by target_level
+        #[serde(default)]
+        splice: Option<SpliceConfig>,  // Cut splices with these settings and normalize each one; the whole file if unset
+    },
+    /// Split the track at its silences, one file per phrase; handled by `SilenceProcessor`
+    SliceBySilence(SilenceConfig),
+    /// Play the audio backwards; handled by `ReverseProcessor`, only used as a pipeline stage
+    Reverse,
+    /// Fade the ends of the audio; handled by `FadeProcessor`, only used as a pipeline stage
+    Fade(Fade),
+    /// Run each stage on the output of the one before, in memory; handled by `PipelineProcessor`
+    Pipeline {
+        stages: Vec<ProcessorConfig>,
+        #[serde(default)]
+        output_format: Option<PcmFormat>,  // Defaults to the input's sample format; the stages' own formats are ignored
     },
 }
 
+impl ProcessorConfig {
+    /// The config's `type` tag, which is also the name its processor is registered under
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ProcessorConfig::Splice(_) => "splice",
+            ProcessorConfig::Regions { .. } => "regions",
+            ProcessorConfig::Normalize { .. } => "normalize",
+            ProcessorConfig::SliceBySilence(_) => "slice_by_silence",
+            ProcessorConfig::Reverse => "reverse",
+            ProcessorConfig::Fade(_) => "fade",
+            ProcessorConfig::Pipeline { .. } => "pipeline",
+        }
+    }
+
+    /// Sample format the output files are written in; the input's format if `None`
+    pub fn output_format(&self) -> Option<PcmFormat> {
+        match self {
+            ProcessorConfig::Splice(config) => config.output_format,
+            ProcessorConfig::Regions { output_format, .. } => *output_format,
+            ProcessorConfig::Normalize { output_format, .. } => *output_format,
+            ProcessorConfig::SliceBySilence(config) => config.output_format,
+            ProcessorConfig::Reverse | ProcessorConfig::Fade(_) => None,
+            ProcessorConfig::Pipeline { output_format, .. } => *output_format,
+        }
+    }
+}
+
+/// Parameters for cutting splices out of a track.
+///
+/// Everything after `reverse` is optional so existing clients keep working.
+/// `duration` may be left out when the length is given in `bars`/`beats` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpliceConfig {
+    #[serde(default)]
+    pub duration: f64,
+    pub count: i32,
+    pub reverse: bool,
+    #[serde(default)]
+    pub output_format: Option<PcmFormat>,  // Defaults to the input's sample format
+    #[serde(default)]
+    pub seed: Option<u64>,  // Same file + seed + params gives identical splices; random if unset
+    #[serde(default)]
+    pub placement: Placement,
+    #[serde(default)]
+    pub clamp: Option<Clamp>,  // What to do when a splice is longer than the audio; fail if unset
+    #[serde(default)]
+    pub window_start: Option<f64>,  // Only take splices after this many seconds; start of track if unset
+    #[serde(default)]
+    pub window_end: Option<f64>,  // Only take splices before this many seconds; end of track if unset
+    #[serde(default)]
+    pub zero_crossing_radius_ms: Option<f64>,  // Snap each splice edge to the nearest zero crossing within this radius; off if unset
+    #[serde(default)]
+    pub fade: Option<Fade>,  // Fade-in/out applied to every splice before writing
+    #[serde(default)]
+    pub min_rms_db: Option<f64>,  // Redraw splices quieter than this RMS level in dBFS; off if unset
+    #[serde(default)]
+    pub max_redraws: Option<u32>,  // Redraws allowed per splice before giving up; defaults to 100
+    #[serde(default)]
+    pub bars: Option<u32>,  // Splice length in bars of the detected tempo; starts snap to downbeats
+    #[serde(default)]
+    pub beats: Option<u32>,  // Extra beats added to `bars`, or the whole length if `bars` is unset
+    #[serde(default)]
+    pub beats_per_bar: Option<u32>,  // Time signature for `bars`; defaults to 4
+    #[serde(default)]
+    pub bpm: Option<f64>,  // Known tempo, skipping detection; only the beat phase is estimated
+}
+
+impl SpliceConfig {
+    /// `count` random splices of `duration` seconds, with every optional setting left unset
+    pub fn new(duration: f64, count: i32) -> Self {
+        Self {
+            duration,
+            count,
+            reverse: false,
+            output_format: None,
+            seed: None,
+            placement: Placement::default(),
+            clamp: None,
+            window_start: None,
+            window_end: None,
+            zero_crossing_radius_ms: None,
+            fade: None,
+            min_rms_db: None,
+            max_redraws: None,
+            bars: None,
+            beats: None,
+            beats_per_bar: None,
+            bpm: None,
+        }
+    }
+
+    /// Splice length in beats when `bars` or `beats` is set, otherwise `None`
+    pub fn beat_length(&self) -> Option<u32> {
+        if self.bars.is_none() && self.beats.is_none() {
+            return None;
+        }
+        let bars = self.bars.unwrap_or(0).saturating_mul(self.beats_per_bar());
+        Some(bars.saturating_add(self.beats.unwrap_or(0)))
+    }
+
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar.unwrap_or(DEFAULT_BEATS_PER_BAR)
+    }
+}
+
+/// How to handle a splice duration longer than the decoded track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Clamp {
+    /// Cut splices to the length of the track
+    Shorten,
+    /// Keep the requested length, filling the end of the splice with silence
+    Pad,
+}
+
+impl FromStr for Clamp {
+    type Err = AudioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "shorten" => Ok(Clamp::Shorten),
+            "pad" => Ok(Clamp::Pad),
+            other => Err(AudioError::ProcessingError(format!("Unknown clamp mode '{}', expected shorten or pad", other))),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessingResult {
     pub files: Vec<PathBuf>,
     pub metadata: ProcessingMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Output of processing a buffer in memory: the clips to write, in order, and what was done
+#[derive(Debug)]
+pub struct ProcessedAudio {
+    pub clips: Vec<Clip>,
+    pub metadata: ProcessingMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingMetadata {
     pub processor_type: String,
     pub input_duration: f64,
     pub sample_rate: u32,
     pub channels: u16,
     pub processing_time_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,  // Seed that drove random splice selection, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<TimeWindow>,  // Part of the track splices were taken from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub boundaries: Vec<SpliceBoundary>,  // Where each splice was cut after zero-crossing snapping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub onsets_detected: Option<usize>,  // Usable onsets found in the window, for onset placement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,  // Tempo of the beat grid, for splices measured in bars/beats
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,  // Span of the source each file was cut from, when slicing by silence
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loudness: Vec<Loudness>,  // Loudness of each normalized file before and after
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<ProcessingMetadata>,  // What each stage of a pipeline did, in order
 }
 
-pub trait AudioProcessor {
-    fn process(&self, input_path: &str, output_dir: &str, config: &ProcessorConfig) -> AudioResult<ProcessingResult>;
+impl ProcessingMetadata {
+    /// Metadata for `processor_type` run on `audio`, with nothing else to report yet
+    pub fn new(processor_type: &str, audio: &AudioBuffer) -> Self {
+        Self {
+            processor_type: processor_type.to_string(),
+            input_duration: audio.duration(),
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            processing_time_ms: 0,
+            seed: None,
+            window: None,
+            boundaries: Vec::new(),
+            onsets_detected: None,
+            bpm: None,
+            segments: Vec::new(),
+            loudness: Vec::new(),
+            stages: Vec::new(),
+        }
+    }
+}
+
+/// Where a splice was placed and where it was actually cut, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpliceBoundary {
+    pub file: String,
+    pub requested_start: f64,
+    pub requested_end: f64,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Part of the source written to `file`, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub file: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Integrated loudness of a normalized file in LUFS; `None` where it is too short or quiet to measure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Loudness {
+    pub file: String,
+    pub input_lufs: Option<f64>,
+    pub output_lufs: Option<f64>,
+    pub gain_db: f64,
+    pub true_peak_db: f64,  // Of the output, in dBTP
+}
+
+/// A span of the source in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Create the RNG used to pick splice positions.
+///
+/// Returns the seed alongside the RNG so it can be reported back; when no seed is
+/// given a random one is drawn. ChaCha8 is used rather than `StdRng` because its
+/// output is guaranteed not to change between `rand` releases.
+pub fn splice_rng(seed: Option<u64>) -> (ChaCha8Rng, u64) {
+    let seed = seed.unwrap_or_else(rand::random);
+    (ChaCha8Rng::seed_from_u64(seed), seed)
+}
+
+/// Audio held in memory with the file name it will be written under
+pub(crate) type Clip = (String, AudioBuffer);
+
+/// Write each named clip into `output_dir`, returning the paths in order
+pub(crate) fn write_clips(clips: &[Clip], output_dir: &Path, output_format: PcmFormat) -> AudioResult<Vec<PathBuf>> {
+    clips
+        .iter()
+        .map(|(file_name, clip)| {
+            let output_path = output_dir.join(file_name);
+            clip.write_wav(&output_path, output_format)?;
+            Ok(output_path)
+        })
+        .collect()
+}
+
+pub trait AudioProcessor: Send + Sync {
+    /// Process `audio` in memory, returning the output clips without touching the filesystem
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio>;
     fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()>;
     fn processor_type(&self) -> &'static str;
+
+    /// Process `input_path`, writing output files into the workspace's output directory.
+    ///
+    /// Decodes the file within the limits in `options`, runs `process_buffer` and writes
+    /// each clip in the configured output format; the reported time includes decoding and writing.
+    fn process(
+        &self,
+        input_path: &Path,
+        workspace: &Workspace,
+        config: &ProcessorConfig,
+        options: DecodeOptions,
+    ) -> AudioResult<ProcessingResult> {
+        let start_time = Instant::now();
+
+        let audio = AudioSource::open(input_path)?.with_options(options).decode()?;
+        let processed = self.process_buffer(&audio, config)?;
+        let output_format = config.output_format().unwrap_or(audio.format);
+        let files = write_clips(&processed.clips, &workspace.output_dir(), output_format)?;
+
+        let mut metadata = processed.metadata;
+        metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok(ProcessingResult { files, metadata })
+    }
+}
+
+/// The processor that handles `config`, from the registry
+pub fn processor_for(config: &ProcessorConfig) -> AudioResult<Box<dyn AudioProcessor>> {
+    Ok(registry::lookup(config.type_name())?.create())
 }
\ No newline at end of file
@@ -1,197 +1,185 @@
-use std::path::PathBuf;
 use std::time::Instant;
-use hound::{WavReader, WavWriter};
-use rand::Rng;
 use log::info;
 
+use crate::audio::AudioBuffer;
+use crate::dsp::limiter::Limiter;
+use crate::dsp::level::{amplitude_to_db, db_to_amplitude};
+use crate::dsp::loudness::integrated_loudness;
+use crate::dsp::true_peak::{true_peak, true_peak_db};
 use crate::errors::{AudioError, AudioResult};
-use super::{AudioProcessor, ProcessorConfig, ProcessingResult, ProcessingMetadata};
+use super::{AudioProcessor, ProcessorConfig, ProcessedAudio, ProcessingMetadata, Loudness, SpliceConfig};
+use super::splice::SpliceProcessor;
+
+/// Splice length in seconds when the multipart `applyToSplices` flag is set without a length
+pub const DEFAULT_SPLICE_DURATION: f64 = 2.0;
+/// Number of splices when the multipart `applyToSplices` flag is set without a count
+pub const DEFAULT_SPLICE_COUNT: i32 = 5;
 
 pub struct NormalizeProcessor;
 
+/// What each file or splice is normalized to
+struct Target {
+    level: f64,
+    lufs: Option<f64>,
+    true_peak: bool,
+    limiter: Option<Limiter>,
+}
+
 impl NormalizeProcessor {
     pub fn new() -> Self {
         Self
     }
 
-    /// Find the peak (maximum absolute value) in a set of samples
-    fn find_peak(samples: &[i16]) -> f64 {
-        samples
+    /// Find the peak (maximum absolute value) across all channels
+    fn find_peak(audio: &AudioBuffer) -> f64 {
+        audio.samples
             .iter()
             .map(|&sample| (sample as f64).abs())
             .fold(0.0, f64::max)
-            / i16::MAX as f64  // Convert to 0.0-1.0 range
     }
 
-    /// Apply normalization gain to samples
-    fn apply_gain(samples: &mut [i16], gain: f64) {
-        for sample in samples {
-            let normalized = (*sample as f64 * gain).round();
-            *sample = normalized.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    /// Apply the same normalization gain to every channel so the balance between them is kept.
+    ///
+    /// The limiter, if any, runs on the result before anything over full scale is clipped.
+    fn apply_gain(audio: &mut AudioBuffer, gain: f64, limiter: Option<Limiter>) {
+        for sample in audio.samples.iter_mut() {
+            *sample = (*sample as f64 * gain) as f32;
         }
-    }
-
-    /// Normalize a single file
-    fn normalize_file(input_path: &str, output_path: &PathBuf, target_level: f64) -> AudioResult<()> {
-        let mut reader = WavReader::open(input_path).map_err(|e| AudioError::WavError(e))?;
-        let spec = reader.spec();
-        
-        // Read all samples into memory
-        let samples: Result<Vec<i16>, _> = reader.samples::<i16>().collect();
-        let mut samples = samples.map_err(|e| AudioError::WavError(e))?;
-        
-        if samples.is_empty() {
-            return Err(AudioError::ProcessingError("No audio data found".to_string()));
+        if let Some(limiter) = limiter {
+            let reduction = limiter.apply(audio);
+            info!("Limiter: ceiling={:.1} dBTP, max reduction={:.2} dB", limiter.ceiling_db, reduction);
+        }
+        for sample in audio.samples.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
         }
+    }
 
-        // Find peak level
-        let peak_level = Self::find_peak(&samples);
-        
+    /// Gain that brings `audio` to the target.
+    ///
+    /// Without `target_lufs` the peak is brought to `target_level`. With it, the
+    /// integrated loudness is brought to `target_lufs`, but never so far that the
+    /// peak passes `target_level` unless a limiter is there to catch it. The peak is
+    /// the oversampled true peak when `true_peak` is set.
+    fn normalization_gain(audio: &AudioBuffer, target: &Target) -> AudioResult<f64> {
+        let Target { level: target_level, lufs: target_lufs, .. } = *target;
+        let peak_level = if target.true_peak { true_peak(audio) } else { Self::find_peak(audio) };
         if peak_level == 0.0 {
             return Err(AudioError::ProcessingError("Audio is silent (no signal detected)".to_string()));
         }
+        let peak_gain = target_level / peak_level;
 
-        // Calculate normalization gain
-        let gain = target_level / peak_level;
-        
-        info!("Normalizing: peak={:.3}, target={:.3}, gain={:.3}x", 
-              peak_level, target_level, gain);
+        let Some(target_lufs) = target_lufs else {
+            info!("Normalizing: peak={:.3}, target={:.3}, gain={:.3}x", peak_level, target_level, peak_gain);
+            return Ok(peak_gain);
+        };
 
-        // Apply gain to all samples
-        Self::apply_gain(&mut samples, gain);
+        let loudness = integrated_loudness(audio).ok_or_else(|| AudioError::ProcessingError(
+            "Cannot measure loudness: audio is shorter than 400 ms or quieter than -70 LUFS".to_string()
+        ))?;
+        let loudness_gain = db_to_amplitude(target_lufs - loudness);
+        let gain = if target.limiter.is_some() { loudness_gain } else { loudness_gain.min(peak_gain) };
 
-        // Write normalized audio
-        let mut writer = WavWriter::create(output_path, spec).map_err(|e| AudioError::WavError(e))?;
-        for sample in samples {
-            writer.write_sample(sample).map_err(|e| AudioError::WavError(e))?;
+        info!("Normalizing: loudness={:.1} LUFS, target={:.1} LUFS, peak={:.3}, ceiling={:.3}, gain={:.3}x",
+              loudness, target_lufs, peak_level, target_level, gain);
+        Ok(gain)
+    }
+
+    /// Apply `gain` and the limiter, recording the loudness before and after
+    fn apply_measured_gain(audio: &mut AudioBuffer, gain: f64, limiter: Option<Limiter>, file: String) -> Loudness {
+        let input_lufs = integrated_loudness(audio);
+        Self::apply_gain(audio, gain, limiter);
+        Loudness {
+            file,
+            input_lufs,
+            output_lufs: integrated_loudness(audio),
+            gain_db: amplitude_to_db(gain),
+            true_peak_db: true_peak_db(audio),
         }
+    }
 
-        Ok(())
-    }
-
-    /// Create normalized splices (like the splice processor, but with normalization)
-    fn create_normalized_splices(
-        input_path: &str, 
-        output_dir: &str, 
-        target_level: f64,
-        splice_duration: f64,
-        splice_count: i32,
-        apply_to_splices: bool
-    ) -> AudioResult<Vec<PathBuf>> {
-        std::fs::create_dir_all(output_dir).map_err(|e| AudioError::IoError(e))?;
-        let mut reader = WavReader::open(input_path).map_err(|e| AudioError::WavError(e))?;
-        let spec = reader.spec();
-        let total_duration = reader.duration() as f64 / spec.sample_rate as f64;
-
-        let mut rng = rand::thread_rng();
-        let mut splice_files = Vec::new();
-
-        for i in 0..splice_count {
-            let start_time = rng.gen_range(0.0..total_duration - splice_duration);
-            let start_sample = (start_time * spec.sample_rate as f64) as u32;
-            let splice_samples_count = (splice_duration * spec.sample_rate as f64) as u32;
-
-            let output_path = PathBuf::from(output_dir).join(format!("normalized_splice_{}.wav", i));
-            let mut writer = WavWriter::create(&output_path, spec).map_err(|e| AudioError::WavError(e))?;
-            
-            reader.seek(start_sample).map_err(|e| AudioError::IoError(e))?;
-            
-            let mut samples = Vec::with_capacity(splice_samples_count as usize);
-            for _ in 0..splice_samples_count {
-                if let Some(sample) = reader.samples::<i16>().next() {
-                    let sample_value = sample.map_err(|e| AudioError::WavError(e))?;
-                    samples.push(sample_value);
-                } else {
-                    break;
-                }
-            }
-            
-            if samples.is_empty() {
-                continue;
-            }
+    /// Normalize the whole of `audio`, naming the result `file_name`
+    fn normalize_whole(audio: &AudioBuffer, file_name: &str, target: &Target) -> AudioResult<(AudioBuffer, Loudness)> {
+        let mut normalized = audio.clone();
+        
+        if normalized.frame_count() == 0 {
+            return Err(AudioError::ProcessingError("No audio data found".to_string()));
+        }
 
-            // Apply normalization if requested
-            if apply_to_splices {
-                let peak_level = Self::find_peak(&samples);
-                if peak_level > 0.0 {
-                    let gain = target_level / peak_level;
-                    Self::apply_gain(&mut samples, gain);
-                }
-            }
-            
-            for sample in samples {
-                writer.write_sample(sample).map_err(|e| AudioError::WavError(e))?;
-            }
+        let gain = Self::normalization_gain(&normalized, target)?;
+        let loudness = Self::apply_measured_gain(&mut normalized, gain, target.limiter, file_name.to_string());
+        Ok((normalized, loudness))
+    }
+
+    /// Cut splices exactly as the splice processor would, then normalize each one on its own
+    fn create_normalized_splices(audio: &AudioBuffer, target: &Target, splice_config: &SpliceConfig) -> AudioResult<ProcessedAudio> {
+        let mut output = SpliceProcessor::new().process_buffer(audio, &ProcessorConfig::Splice(splice_config.clone()))?;
+        let mut loudness = Vec::new();
 
-            splice_files.push(output_path);
+        for (file_name, splice) in output.clips.iter_mut() {
+            *file_name = format!("normalized_{}", file_name);
+
+            // Silent or unmeasurable splices are written as they are
+            if let Ok(gain) = Self::normalization_gain(splice, target) {
+                loudness.push(Self::apply_measured_gain(splice, gain, target.limiter, file_name.clone()));
+            }
         }
 
-        Ok(splice_files)
+        output.metadata.loudness = loudness;
+        Ok(output)
     }
 }
 
 impl AudioProcessor for NormalizeProcessor {
-    fn process(&self, input_path: &str, output_dir: &str, config: &ProcessorConfig) -> AudioResult<ProcessingResult> {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
         let start_time = Instant::now();
-        
-        let (target_level, apply_to_splices) = match config {
-            ProcessorConfig::Normalize { target_level, apply_to_splices } => (*target_level, *apply_to_splices),
-            _ => return Err(AudioError::ProcessingError("Invalid config for NormalizeProcessor".to_string())),
-        };
 
         self.validate_config(config)?;
-        
-        std::fs::create_dir_all(output_dir).map_err(|e| AudioError::IoError(e))?;
-        
-        // Read input file metadata
-        let reader = WavReader::open(input_path).map_err(|e| AudioError::WavError(e))?;
-        let spec = reader.spec();
-        let total_duration = reader.duration() as f64 / spec.sample_rate as f64;
-        drop(reader);
-
-        info!("Processing normalize - Target level: {}, Apply to splices: {}", target_level, apply_to_splices);
-
-        let output_files = if apply_to_splices {
-            // Create normalized splices (this is a hybrid mode - creates splices AND normalizes them)
-            // For simplicity, we'll create 5 splices of 2 seconds each
-            // In a real implementation, you might want splice parameters in the config
-            Self::create_normalized_splices(
-                input_path, 
-                output_dir, 
-                target_level,
-                2.0,  // 2 second splices
-                5,    // 5 splices
-                true
-            )?
+        let ProcessorConfig::Normalize {
+            target_level, target_lufs, true_peak, limiter, splice, ..
+        } = config else {
+            return Err(AudioError::ProcessingError("Invalid config for NormalizeProcessor".to_string()));
+        };
+        let target = Target { level: *target_level, lufs: *target_lufs, true_peak: *true_peak, limiter: *limiter };
+
+        info!("Processing normalize - Target level: {}{}, Target loudness: {:?}, Limiter: {:?}, Apply to splices: {}",
+              target.level, if target.true_peak { " (true peak)" } else { "" }, target.lufs, target.limiter, splice.is_some());
+
+        let mut output = if let Some(splice) = splice {
+            // Hybrid mode: cut splices like the splice processor, then normalize each of them
+            Self::create_normalized_splices(audio, &target, splice)?
         } else {
             // Just normalize the entire file
-            let output_path = PathBuf::from(output_dir).join("normalized_audio.wav");
-            Self::normalize_file(input_path, &output_path, target_level)?;
-            vec![output_path]
+            let file_name = "normalized_audio.wav";
+            let (normalized, loudness) = Self::normalize_whole(audio, file_name, &target)?;
+            ProcessedAudio {
+                clips: vec![(file_name.to_string(), normalized)],
+                metadata: ProcessingMetadata { loudness: vec![loudness], ..ProcessingMetadata::new(self.processor_type(), audio) },
+            }
         };
 
-        let processing_time = start_time.elapsed();
-        
-        Ok(ProcessingResult {
-            files: output_files,
-            metadata: ProcessingMetadata {
-                processor_type: self.processor_type().to_string(),
-                input_duration: total_duration,
-                sample_rate: spec.sample_rate,
-                channels: spec.channels,
-                processing_time_ms: processing_time.as_millis() as u64,
-            },
-        })
+        output.metadata.processor_type = self.processor_type().to_string();
+        output.metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok(output)
     }
 
     fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
         match config {
-            ProcessorConfig::Normalize { target_level, .. } => {
-                if *target_level <= 0.0 || *target_level > 1.0 {
+            ProcessorConfig::Normalize { target_level, target_lufs, limiter, splice, .. } => {
+                if !target_level.is_finite() || *target_level <= 0.0 || *target_level > 1.0 {
                     return Err(AudioError::ProcessingError(
                         "target_level must be between 0.0 and 1.0 (where 1.0 = maximum level)".to_string()
                     ));
                 }
+                if matches!(target_lufs, Some(lufs) if !(-70.0..=0.0).contains(lufs)) {
+                    return Err(AudioError::ProcessingError("target_lufs must be between -70 and 0 LUFS".to_string()));
+                }
+                limiter.map_or(Ok(()), |limiter| limiter.validate())?;
+                if let Some(splice) = splice {
+                    if splice.output_format.is_some() {
+                        return Err(AudioError::ProcessingError("set output_format on the normalize config, not its splice settings".to_string()));
+                    }
+                    SpliceProcessor::new().validate_config(&ProcessorConfig::Splice(splice.clone()))?;
+                }
                 Ok(())
             },
             _ => Err(AudioError::ProcessingError("Invalid config for NormalizeProcessor".to_string())),
@@ -201,4 +189,151 @@ impl AudioProcessor for NormalizeProcessor {
     fn processor_type(&self) -> &'static str {
         "normalize"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioSource, DecodeOptions, PcmFormat};
+    use crate::processors::{Placement, ProcessingResult};
+    use crate::workspace::Workspace;
+
+    /// Three seconds of a stereo 1 kHz sine with the given peak
+    fn write_sine(workspace: &Workspace, peak: f32) {
+        let sample_rate = 48000;
+        let audio = AudioBuffer {
+            sample_rate,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: (0..sample_rate as usize * 3)
+                .flat_map(|frame| {
+                    let sample = peak * (std::f32::consts::TAU * 1000.0 * frame as f32 / sample_rate as f32).sin();
+                    [sample, sample]
+                })
+                .collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+    }
+
+    fn normalize_to_lufs(workspace: &Workspace, target_level: f64, target_lufs: f64, limiter: Option<Limiter>) -> ProcessingResult {
+        let config = ProcessorConfig::Normalize {
+            target_level,
+            output_format: None,
+            target_lufs: Some(target_lufs),
+            true_peak: true,
+            limiter,
+            splice: None,
+        };
+        NormalizeProcessor::new().process(&workspace.input_path(), workspace, &config, DecodeOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_target_level_must_be_a_level() {
+        for target_level in [0.0, 1.5, f64::NAN] {
+            let config = ProcessorConfig::Normalize {
+                target_level,
+                output_format: None,
+                target_lufs: None,
+                true_peak: false,
+                limiter: None,
+                splice: None,
+            };
+            let result = NormalizeProcessor::new().validate_config(&config);
+            assert!(matches!(result, Err(AudioError::ProcessingError(_))), "{}", target_level);
+        }
+    }
+
+    #[test]
+    fn test_lufs_target_is_reached_unless_the_peak_would_pass_the_ceiling() {
+        let workspace = Workspace::create().unwrap();
+        // A -30 dBFS stereo sine measures -30 LUFS
+        write_sine(&workspace, 0.0316);
+
+        let result = normalize_to_lufs(&workspace, 1.0, -16.0, None);
+        let loudness = &result.metadata.loudness[0];
+        assert_eq!(loudness.file, "normalized_audio.wav");
+        assert!((loudness.input_lufs.unwrap() + 30.0).abs() < 0.1);
+        assert!((loudness.output_lufs.unwrap() + 16.0).abs() < 0.1);
+        let output = AudioSource::open(&result.files[0]).unwrap().decode().unwrap();
+        assert!((integrated_loudness(&output).unwrap() + 16.0).abs() < 0.1);
+
+        // Reaching -6 LUFS would need a peak of -6 dBFS, above the 0.25 (-12 dBFS) ceiling
+        let result = normalize_to_lufs(&workspace, 0.25, -6.0, None);
+        let loudness = &result.metadata.loudness[0];
+        assert!((loudness.output_lufs.unwrap() + 12.0).abs() < 0.1);
+        let output = AudioSource::open(&result.files[0]).unwrap().decode().unwrap();
+        assert!(NormalizeProcessor::find_peak(&output) <= 0.25 + 1e-6);
+    }
+
+    #[test]
+    fn test_limiter_lets_the_lufs_target_through_under_a_true_peak_ceiling() {
+        let workspace = Workspace::create().unwrap();
+        // Quiet tone with a short hot transient that would otherwise set the gain
+        let sample_rate = 48000;
+        let audio = AudioBuffer {
+            sample_rate,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..sample_rate as usize * 3)
+                .map(|frame| {
+                    let level = if (60000..60048).contains(&frame) { 0.9 } else { 0.05 };
+                    level * (std::f32::consts::TAU * 1000.0 * frame as f32 / sample_rate as f32).sin()
+                })
+                .collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let limiter = Limiter { ceiling_db: -1.0, ..Default::default() };
+        let result = normalize_to_lufs(&workspace, 1.0, -14.0, Some(limiter));
+        let loudness = &result.metadata.loudness[0];
+        // Without the limiter the click would hold the output near -28 LUFS; the gain
+        // reduction around it costs a little loudness
+        assert!((loudness.output_lufs.unwrap() + 14.0).abs() < 1.0, "{:?}", loudness);
+        assert!(loudness.true_peak_db <= -1.0 + 0.05, "{:?}", loudness);
+    }
+
+    #[test]
+    fn test_splices_follow_the_splice_settings() {
+        let workspace = Workspace::create().unwrap();
+        write_sine(&workspace, 0.1);
+
+        let splice = SpliceConfig { seed: Some(7), placement: Placement::NonOverlapping, ..SpliceConfig::new(0.5, 3) };
+        let config = ProcessorConfig::Normalize {
+            target_level: 0.5,
+            output_format: None,
+            target_lufs: None,
+            true_peak: false,
+            limiter: None,
+            splice: Some(splice.clone()),
+        };
+        let result = NormalizeProcessor::new().process(&workspace.input_path(), &workspace, &config, DecodeOptions::default()).unwrap();
+
+        let names: Vec<_> = result.files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["normalized_splice_0.wav", "normalized_splice_1.wav", "normalized_splice_2.wav"]);
+        assert_eq!(result.metadata.seed, Some(7));
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(splice.frame_count(), 24000);
+            assert!((NormalizeProcessor::find_peak(&splice) - 0.5).abs() < 1e-3);
+        }
+
+        // Same seed, same splices as the splice processor cuts
+        let audio = AudioSource::open(&workspace.input_path()).unwrap().decode().unwrap();
+        let plain = SpliceProcessor::new().process_buffer(&audio, &ProcessorConfig::Splice(splice.clone())).unwrap();
+        let normalized = AudioSource::open(&result.files[1]).unwrap().decode().unwrap();
+        let expected = &plain.clips[1].1.samples;
+        assert!(normalized.samples.iter().zip(expected).all(|(n, p)| (n - 5.0 * p).abs() < 1e-4));
+
+        // The splices are written in the normalize config's format, so they can't have their own
+        let splice = SpliceConfig { output_format: Some(PcmFormat::Int16), ..splice };
+        let config = ProcessorConfig::Normalize {
+            target_level: 0.5,
+            output_format: None,
+            target_lufs: None,
+            true_peak: false,
+            limiter: None,
+            splice: Some(splice),
+        };
+        assert!(NormalizeProcessor::new().process(&workspace.input_path(), &workspace, &config, DecodeOptions::default()).is_err());
+    }
+}
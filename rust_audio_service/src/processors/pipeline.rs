@@ -0,0 +1,242 @@
+use std::time::Instant;
+use log::info;
+
+use crate::audio::AudioBuffer;
+use crate::errors::{AudioError, AudioResult};
+use super::{processor_for, Clip, AudioProcessor, ProcessorConfig, ProcessedAudio, ProcessingMetadata};
+
+pub struct PipelineProcessor;
+
+impl PipelineProcessor {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Name for `name` cut from `parent`; prefixed with the parent's name when there are several parents
+    fn child_name(parent: &str, name: &str, several: bool) -> String {
+        if several {
+            format!("{}_{}", parent.trim_end_matches(".wav"), name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Seed a stage picks splices with, drawing one if it has none; `None` for stages that aren't random
+    fn stage_seed(stage: &ProcessorConfig) -> Option<u64> {
+        match stage {
+            ProcessorConfig::Splice(config) => Some(config.seed.unwrap_or_else(rand::random)),
+            ProcessorConfig::Normalize { splice: Some(splice), .. } => Some(splice.seed.unwrap_or_else(rand::random)),
+            _ => None,
+        }
+    }
+
+    /// `stage` with its splice seed replaced by `seed`
+    fn with_seed(stage: &ProcessorConfig, seed: u64) -> ProcessorConfig {
+        let mut stage = stage.clone();
+        match &mut stage {
+            ProcessorConfig::Splice(config) => config.seed = Some(seed),
+            ProcessorConfig::Normalize { splice: Some(splice), .. } => splice.seed = Some(seed),
+            _ => {},
+        }
+        stage
+    }
+
+    /// Whether `stage` cuts each clip into pieces rather than changing it in place
+    fn cuts_audio(stage: &ProcessorConfig) -> bool {
+        match stage {
+            ProcessorConfig::Splice(_) | ProcessorConfig::Regions { .. } | ProcessorConfig::SliceBySilence(_) => true,
+            ProcessorConfig::Normalize { splice, .. } => splice.is_some(),
+            ProcessorConfig::Reverse | ProcessorConfig::Fade(_) | ProcessorConfig::Pipeline { .. } => false,
+        }
+    }
+
+    /// Add what one clip reports to the stage's metadata, with file names passed through `rename`
+    fn record_clip(metadata: &mut ProcessingMetadata, mut clip: ProcessingMetadata, rename: impl Fn(&str) -> String) {
+        metadata.window = metadata.window.or(clip.window);
+        metadata.bpm = metadata.bpm.or(clip.bpm);
+        if let Some(onsets) = clip.onsets_detected {
+            metadata.onsets_detected = Some(metadata.onsets_detected.unwrap_or(0) + onsets);
+        }
+        for boundary in clip.boundaries.iter_mut() {
+            boundary.file = rename(&boundary.file);
+        }
+        for segment in clip.segments.iter_mut() {
+            segment.file = rename(&segment.file);
+        }
+        for entry in clip.loudness.iter_mut() {
+            entry.file = rename(&entry.file);
+        }
+        metadata.boundaries.append(&mut clip.boundaries);
+        metadata.segments.append(&mut clip.segments);
+        metadata.loudness.append(&mut clip.loudness);
+    }
+
+    /// Run one stage over every clip, returning the clips for the next stage and what the stage did.
+    ///
+    /// Stages that cut audio up replace each clip with its pieces; the rest change
+    /// clips in place and keep their names. When several clips are cut up, piece
+    /// names are prefixed with the clip they came from so they stay unique. Random
+    /// stages use their seed for the first clip and count up from it for the rest.
+    fn run_stage(stage: &ProcessorConfig, clips: Vec<Clip>) -> AudioResult<(Vec<Clip>, ProcessingMetadata)> {
+        if matches!(stage, ProcessorConfig::Pipeline { .. }) {
+            return Err(AudioError::ProcessingError("Pipelines cannot be nested".to_string()));
+        }
+
+        let start_time = Instant::now();
+        let several = clips.len() > 1;
+        let cuts = Self::cuts_audio(stage);
+        let seed = Self::stage_seed(stage);
+        let processor = processor_for(stage)?;
+
+        let Some((_, first)) = clips.first() else {
+            return Err(AudioError::ProcessingError("Pipeline stage has no audio to work on".to_string()));
+        };
+        let mut metadata = ProcessingMetadata::new(stage.type_name(), first);
+        metadata.input_duration = clips.iter().map(|(_, audio)| audio.duration()).sum();
+        metadata.seed = seed;
+        let mut output = Vec::new();
+
+        for (i, (name, audio)) in clips.into_iter().enumerate() {
+            let stage = match seed {
+                Some(seed) => Self::with_seed(stage, seed.wrapping_add(i as u64)),
+                None => stage.clone(),
+            };
+
+            let processed = processor.process_buffer(&audio, &stage)?;
+            let rename = |file: &str| if cuts { Self::child_name(&name, file, several) } else { name.clone() };
+            Self::record_clip(&mut metadata, processed.metadata, rename);
+            output.extend(processed.clips.into_iter().map(|(file, clip)| (rename(&file), clip)));
+        }
+
+        metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok((output, metadata))
+    }
+
+    /// Run every stage in turn, starting from the whole track as `audio.wav`
+    pub(crate) fn run(audio: AudioBuffer, stages: &[ProcessorConfig]) -> AudioResult<(Vec<Clip>, Vec<ProcessingMetadata>)> {
+        let mut clips = vec![("audio.wav".to_string(), audio)];
+        let mut reports = Vec::new();
+
+        for (i, stage) in stages.iter().enumerate() {
+            let (next, report) = Self::run_stage(stage, clips)?;
+            info!("Pipeline stage {} ({}) gave {} clips in {} ms", i, report.processor_type, next.len(), report.processing_time_ms);
+            if next.is_empty() {
+                return Err(AudioError::ProcessingError(format!("Pipeline stage {} ({}) produced no audio", i, report.processor_type)));
+            }
+            clips = next;
+            reports.push(report);
+        }
+
+        Ok((clips, reports))
+    }
+}
+
+impl AudioProcessor for PipelineProcessor {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
+        let start_time = Instant::now();
+
+        self.validate_config(config)?;
+        let ProcessorConfig::Pipeline { stages, .. } = config else {
+            return Err(AudioError::ProcessingError("Invalid config for PipelineProcessor".to_string()));
+        };
+
+        info!("Processing pipeline - Stages: {:?}", stages.iter().map(ProcessorConfig::type_name).collect::<Vec<_>>());
+
+        let mut metadata = ProcessingMetadata::new(self.processor_type(), audio);
+        let (clips, reports) = Self::run(audio.clone(), stages)?;
+        // The first random stage's seed, so the usual seed header still says something useful
+        metadata.seed = reports.iter().find_map(|report| report.seed);
+        metadata.stages = reports;
+        metadata.processing_time_ms = start_time.elapsed().as_millis() as u64;
+
+        Ok(ProcessedAudio { clips, metadata })
+    }
+
+    fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
+        let ProcessorConfig::Pipeline { stages, .. } = config else {
+            return Err(AudioError::ProcessingError("Invalid config for PipelineProcessor".to_string()));
+        };
+        if stages.is_empty() {
+            return Err(AudioError::ProcessingError("A pipeline needs at least one stage".to_string()));
+        }
+
+        for (i, stage) in stages.iter().enumerate() {
+            let result = match stage {
+                ProcessorConfig::Pipeline { .. } => Err(AudioError::ProcessingError("Pipelines cannot be nested".to_string())),
+                _ => processor_for(stage).and_then(|processor| processor.validate_config(stage)),
+            };
+            result.map_err(|e| AudioError::ProcessingError(format!("Stage {} ({}): {}", i, stage.type_name(), e)))?;
+        }
+        Ok(())
+    }
+
+    fn processor_type(&self) -> &'static str {
+        "pipeline"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioSource, DecodeOptions, PcmFormat};
+    use crate::dsp::fade::Fade;
+    use crate::workspace::Workspace;
+    use crate::processors::{SilenceConfig, SpliceConfig};
+
+    #[test]
+    fn test_stages_run_in_order_on_every_clip() {
+        let workspace = Workspace::create().unwrap();
+        // Two quiet ramps separated by silence, 8 kHz mono
+        let sample_rate = 8000;
+        let ramp: Vec<f32> = (0..8000).map(|n| 0.1 * n as f32 / 8000.0 + 0.01).collect();
+        let mut samples = ramp.clone();
+        samples.extend(vec![0.0; 8000]);
+        samples.extend(&ramp);
+        let audio = AudioBuffer { sample_rate, channels: 1, format: PcmFormat::Float32, samples };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let stages = vec![
+            ProcessorConfig::SliceBySilence(SilenceConfig { padding_ms: 0.0, ..Default::default() }),
+            ProcessorConfig::Splice(SpliceConfig { seed: Some(1), ..SpliceConfig::new(0.25, 2) }),
+            ProcessorConfig::Reverse,
+            ProcessorConfig::Normalize {
+                target_level: 0.5,
+                output_format: None,
+                target_lufs: None,
+                true_peak: false,
+                limiter: None,
+                splice: None,
+            },
+            ProcessorConfig::Fade(Fade { out_ms: 10.0, ..Default::default() }),
+        ];
+        let config = ProcessorConfig::Pipeline { stages, output_format: None };
+        let result = PipelineProcessor::new().process(&workspace.input_path(), &workspace, &config, DecodeOptions::default()).unwrap();
+
+        let names: Vec<_> = result.files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["segment_0_splice_0.wav", "segment_0_splice_1.wav", "segment_1_splice_0.wav", "segment_1_splice_1.wav"]);
+
+        let types: Vec<_> = result.metadata.stages.iter().map(|stage| stage.processor_type.as_str()).collect();
+        assert_eq!(types, vec!["slice_by_silence", "splice", "reverse", "normalize", "fade"]);
+        assert_eq!(result.metadata.seed, Some(1));
+        assert_eq!(result.metadata.stages[3].loudness[0].file, "segment_0_splice_0.wav");
+
+        for file in &result.files {
+            let clip = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(clip.frame_count(), 2000);
+            // Reversed so the ramp falls from the 0.5 peak, then faded out to silence
+            assert!((clip.samples[0] - 0.5).abs() < 1e-3);
+            assert!(clip.samples[0] > clip.samples[1000]);
+            assert_eq!(clip.samples[1999], 0.0);
+        }
+    }
+
+    #[test]
+    fn test_nested_and_empty_pipelines_are_rejected() {
+        let processor = PipelineProcessor::new();
+        let empty = ProcessorConfig::Pipeline { stages: Vec::new(), output_format: None };
+        assert!(processor.validate_config(&empty).is_err());
+
+        let nested = ProcessorConfig::Pipeline { stages: vec![empty.clone()], output_format: None };
+        assert!(processor.validate_config(&nested).is_err());
+    }
+}
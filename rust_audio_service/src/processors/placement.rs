@@ -0,0 +1,310 @@
+use std::str::FromStr;
+use rand::Rng;
+use rand::seq::index;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AudioError, AudioResult};
+
+/// Most pieces `SequentialChop`, or slicing by silence, may cut a track into, so a tiny length can't flood the output
+pub(crate) const MAX_CHOP_PIECES: usize = 10_000;
+
+/// How splice start positions are chosen within the track.
+///
+/// Serialized as a string (`"non_overlapping"`) except for `MinGapSeconds` and
+/// `Onsets`, which carry settings: `{"min_gap_seconds": 0.5}`,
+/// `{"onsets": {"sensitivity": 0.7}}`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    /// Independent uniform random starts; splices may overlap
+    #[default]
+    Random,
+    /// Random starts, but no two splices share any audio
+    NonOverlapping,
+    /// Random starts with at least this many seconds between splices
+    MinGapSeconds(f64),
+    /// Starts spread evenly from the beginning to the end of the track
+    EvenlySpaced,
+    /// Cut the whole track into consecutive pieces; `count` is ignored
+    SequentialChop,
+    /// Start each splice on a randomly chosen detected onset (drum hit, note attack)
+    Onsets(OnsetSettings),
+}
+
+/// Tuning for onset detection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnsetSettings {
+    pub sensitivity: f64,  // 0.0 finds only the strongest hits, 1.0 nearly every peak
+    pub min_interval_ms: f64,  // Onsets closer than this to the previous one are ignored
+}
+
+impl Default for OnsetSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.5,
+            min_interval_ms: 50.0,
+        }
+    }
+}
+
+impl FromStr for Placement {
+    type Err = AudioError;
+
+    /// Parse the unit strategies by name; `min_gap_seconds` needs a gap and is
+    /// built directly as `Placement::MinGapSeconds`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "random" => Ok(Placement::Random),
+            "non_overlapping" => Ok(Placement::NonOverlapping),
+            "evenly_spaced" => Ok(Placement::EvenlySpaced),
+            "sequential_chop" => Ok(Placement::SequentialChop),
+            "onsets" => Ok(Placement::Onsets(OnsetSettings::default())),
+            other => Err(AudioError::ProcessingError(format!(
+                "Unknown placement '{}', expected one of random, non_overlapping, min_gap_seconds, evenly_spaced, sequential_chop, onsets", other
+            ))),
+        }
+    }
+}
+
+impl Placement {
+    pub fn validate(&self) -> AudioResult<()> {
+        match self {
+            Placement::MinGapSeconds(gap) if !gap.is_finite() || *gap < 0.0 => Err(AudioError::InvalidDuration(
+                "min_gap_seconds must be zero or positive".to_string()
+            )),
+            Placement::Onsets(settings) if !(0.0..=1.0).contains(&settings.sensitivity) => Err(AudioError::ProcessingError(
+                "onset sensitivity must be between 0.0 and 1.0".to_string()
+            )),
+            Placement::Onsets(settings) if !settings.min_interval_ms.is_finite() || settings.min_interval_ms < 0.0 => {
+                Err(AudioError::InvalidDuration("min_interval_ms must be zero or positive".to_string()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Choose the start frame of each splice.
+    ///
+    /// All positions are in frames. Returns an error if `count` splices of
+    /// `splice_frames` cannot be placed in `total_frames` under this strategy.
+    /// `Onsets` needs the audio itself and is planned with `pick_onsets` instead.
+    pub fn plan_starts<R: Rng>(
+        &self,
+        total_frames: usize,
+        splice_frames: usize,
+        count: usize,
+        sample_rate: u32,
+        rng: &mut R,
+    ) -> AudioResult<Vec<usize>> {
+        if splice_frames == 0 || splice_frames > total_frames {
+            return Err(AudioError::SpliceTooLong {
+                requested: splice_frames as f64 / sample_rate as f64,
+                available: total_frames as f64 / sample_rate as f64,
+            });
+        }
+        let max_start = total_frames - splice_frames;
+
+        match self {
+            Placement::Random => Ok((0..count).map(|_| rng.gen_range(0..=max_start)).collect()),
+            Placement::NonOverlapping => Self::plan_with_gap(total_frames, splice_frames, count, 0, rng),
+            Placement::MinGapSeconds(gap) => {
+                let gap_frames = (gap * sample_rate as f64).round() as usize;
+                Self::plan_with_gap(total_frames, splice_frames, count, gap_frames, rng)
+            },
+            Placement::EvenlySpaced => {
+                if count.saturating_mul(splice_frames) > total_frames {
+                    return Err(Self::does_not_fit(total_frames, splice_frames, count, 0));
+                }
+                if count == 1 {
+                    return Ok(vec![0]);
+                }
+                let step = max_start as f64 / (count - 1) as f64;
+                Ok((0..count).map(|i| (i as f64 * step).round() as usize).collect())
+            },
+            Placement::SequentialChop => {
+                let pieces = total_frames.div_ceil(splice_frames);
+                if pieces > MAX_CHOP_PIECES {
+                    return Err(AudioError::InvalidSpliceCount(format!(
+                        "sequential_chop would cut {} pieces, at most {} are allowed; use a longer splice duration",
+                        pieces, MAX_CHOP_PIECES
+                    )));
+                }
+                Ok((0..total_frames).step_by(splice_frames).collect())
+            },
+            Placement::Onsets(_) => Err(AudioError::ProcessingError(
+                "onset placement must be planned from detected onsets".to_string()
+            )),
+        }
+    }
+
+    /// Choose `count` distinct onsets at random, returned in time order
+    pub fn pick_onsets<R: Rng>(onsets: &[usize], count: usize, rng: &mut R) -> AudioResult<Vec<usize>> {
+        if count > onsets.len() {
+            return Err(AudioError::InvalidSpliceCount(format!(
+                "Only {} onsets with room for a full splice were found, {} requested; try a higher sensitivity or a shorter min_interval_ms",
+                onsets.len(), count
+            )));
+        }
+
+        let mut picked: Vec<usize> = index::sample(rng, onsets.len(), count)
+            .into_iter()
+            .map(|i| onsets[i])
+            .collect();
+        picked.sort_unstable();
+        Ok(picked)
+    }
+
+    /// Random non-overlapping placement with at least `gap_frames` between splices.
+    ///
+    /// The slack left after laying splices and gaps end to end is split at `count`
+    /// random points; sorting those and adding back the fixed spacing gives starts
+    /// distributed uniformly over all valid arrangements.
+    fn plan_with_gap<R: Rng>(
+        total_frames: usize,
+        splice_frames: usize,
+        count: usize,
+        gap_frames: usize,
+        rng: &mut R,
+    ) -> AudioResult<Vec<usize>> {
+        let required = count
+            .saturating_mul(splice_frames)
+            .saturating_add(count.saturating_sub(1).saturating_mul(gap_frames));
+        if required > total_frames {
+            return Err(Self::does_not_fit(total_frames, splice_frames, count, gap_frames));
+        }
+
+        let slack = total_frames - required;
+        // A gap too long to add only fits with a single splice, whose stride is multiplied by zero
+        let stride = splice_frames.saturating_add(gap_frames);
+        let mut offsets: Vec<usize> = (0..count).map(|_| rng.gen_range(0..=slack)).collect();
+        offsets.sort_unstable();
+
+        Ok(offsets
+            .into_iter()
+            .enumerate()
+            .map(|(i, offset)| offset + i * stride)
+            .collect())
+    }
+
+    /// Whether starts are drawn at random, so a rejected splice can be drawn again
+    pub fn is_random(&self) -> bool {
+        matches!(self, Placement::Random | Placement::NonOverlapping | Placement::MinGapSeconds(_))
+    }
+
+    /// Draw a replacement start for `starts[index]`.
+    ///
+    /// The candidate is uniform over the track; for spaced strategies it is
+    /// discarded (`None`) if it would come too close to any other splice, and the
+    /// caller should try again. Fixed strategies always return `None`.
+    pub fn redraw_start<R: Rng>(
+        &self,
+        starts: &[usize],
+        index: usize,
+        total_frames: usize,
+        splice_frames: usize,
+        sample_rate: u32,
+        rng: &mut R,
+    ) -> Option<usize> {
+        let gap_frames = match self {
+            Placement::Random => None,
+            Placement::NonOverlapping => Some(0),
+            Placement::MinGapSeconds(gap) => Some((gap * sample_rate as f64).round() as usize),
+            Placement::EvenlySpaced | Placement::SequentialChop | Placement::Onsets(_) => return None,
+        };
+
+        let candidate = rng.gen_range(0..=total_frames.checked_sub(splice_frames)?);
+        let spacing = splice_frames.saturating_add(gap_frames.unwrap_or(0));
+        let clear = gap_frames.is_none() || starts
+            .iter()
+            .enumerate()
+            .all(|(i, &start)| i == index || start.abs_diff(candidate) >= spacing);
+
+        clear.then_some(candidate)
+    }
+
+    fn does_not_fit(total_frames: usize, splice_frames: usize, count: usize, gap_frames: usize) -> AudioError {
+        // A gap of any length still leaves room for one splice, and saturating keeps that answer for huge gaps
+        let max_count = total_frames.saturating_add(gap_frames) / splice_frames.saturating_add(gap_frames);
+        AudioError::InvalidSpliceCount(format!(
+            "Cannot fit {} non-overlapping splices with the requested spacing, at most {} fit in this track", count, max_count
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::splice_rng;
+
+    /// Sample rate used throughout, so frames read as milliseconds
+    const RATE: u32 = 1000;
+
+    fn plan(placement: Placement, total_frames: usize, splice_frames: usize, count: usize) -> AudioResult<Vec<usize>> {
+        placement.plan_starts(total_frames, splice_frames, count, RATE, &mut splice_rng(Some(1)).0)
+    }
+
+    #[test]
+    fn test_random_starts_leave_room_for_the_splice() {
+        let starts = plan(Placement::Random, 1000, 100, 50).unwrap();
+        assert_eq!(starts.len(), 50);
+        assert!(starts.iter().all(|&start| start <= 900));
+
+        assert!(matches!(plan(Placement::Random, 1000, 1001, 1), Err(AudioError::SpliceTooLong { .. })));
+        assert!(matches!(plan(Placement::Random, 1000, 0, 1), Err(AudioError::SpliceTooLong { .. })));
+    }
+
+    #[test]
+    fn test_spaced_placements_keep_splices_apart() {
+        for (placement, spacing) in [(Placement::NonOverlapping, 100), (Placement::MinGapSeconds(0.05), 150)] {
+            let starts = plan(placement, 1000, 100, 5).unwrap();
+            assert!(starts.windows(2).all(|pair| pair[1] - pair[0] >= spacing), "{:?}: {:?}", placement, starts);
+            assert!(starts.iter().all(|&start| start <= 900));
+        }
+
+        // Ten splices exactly fill the track; an eleventh, or any gap, can't fit
+        assert_eq!(plan(Placement::NonOverlapping, 1000, 100, 10).unwrap(), (0..10).map(|i| i * 100).collect::<Vec<_>>());
+        assert!(matches!(plan(Placement::NonOverlapping, 1000, 100, 11), Err(AudioError::InvalidSpliceCount(_))));
+        assert!(matches!(plan(Placement::MinGapSeconds(0.01), 1000, 100, 10), Err(AudioError::InvalidSpliceCount(_))));
+    }
+
+    #[test]
+    fn test_oversized_gaps_leave_room_for_one_splice() {
+        // 1e20 seconds saturates to usize::MAX frames
+        let result = plan(Placement::MinGapSeconds(1e20), 1000, 100, 2);
+        assert!(matches!(result, Err(AudioError::InvalidSpliceCount(ref message)) if message.contains("at most 1 fit")));
+
+        let starts = plan(Placement::MinGapSeconds(1e20), 1000, 100, 1).unwrap();
+        assert!(starts.len() == 1 && starts[0] <= 900);
+    }
+
+    #[test]
+    fn test_evenly_spaced_runs_from_start_to_end() {
+        assert_eq!(plan(Placement::EvenlySpaced, 1000, 100, 4).unwrap(), vec![0, 300, 600, 900]);
+        assert_eq!(plan(Placement::EvenlySpaced, 1000, 100, 1).unwrap(), vec![0]);
+        assert!(matches!(plan(Placement::EvenlySpaced, 1000, 100, 11), Err(AudioError::InvalidSpliceCount(_))));
+    }
+
+    #[test]
+    fn test_sequential_chop_covers_the_track_up_to_the_piece_limit() {
+        // `count` is ignored; the last piece is whatever is left
+        assert_eq!(plan(Placement::SequentialChop, 1000, 300, 1).unwrap(), vec![0, 300, 600, 900]);
+        assert_eq!(plan(Placement::SequentialChop, MAX_CHOP_PIECES, 1, 1).unwrap().len(), MAX_CHOP_PIECES);
+
+        let result = plan(Placement::SequentialChop, MAX_CHOP_PIECES + 1, 1, 1);
+        assert!(matches!(result, Err(AudioError::InvalidSpliceCount(_))));
+    }
+
+    #[test]
+    fn test_onsets_are_picked_from_the_detected_ones() {
+        assert!(plan(Placement::Onsets(OnsetSettings::default()), 1000, 100, 2).is_err());
+
+        let onsets = [10, 200, 350, 600, 800];
+        let picked = Placement::pick_onsets(&onsets, 3, &mut splice_rng(Some(1)).0).unwrap();
+        assert_eq!(picked.len(), 3);
+        assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(picked.iter().all(|start| onsets.contains(start)));
+
+        let result = Placement::pick_onsets(&onsets, 6, &mut splice_rng(Some(1)).0);
+        assert!(matches!(result, Err(AudioError::InvalidSpliceCount(_))));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::errors::{AudioError, AudioResult};
+
+/// An exact span of the source to extract, e.g. 1:32 to 1:36.
+///
+/// `start` and `end` accept seconds (`92.5`) or a timestamp string
+/// (`"1:32.5"`, `"0:01:32"`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Region {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: f64,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: f64,
+    #[serde(default)]
+    pub name: Option<String>,  // File name inside the ZIP; region_<n>.wav if unset
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+/// Check every region lies inside the track and return the output file name for each
+pub fn validate_regions(regions: &[Region], total_duration: f64) -> AudioResult<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut names = Vec::with_capacity(regions.len());
+
+    for (i, region) in regions.iter().enumerate() {
+        let name = file_name(region.name.as_deref(), i);
+
+        if !region.start.is_finite() || !region.end.is_finite() || region.start < 0.0 {
+            return Err(AudioError::InvalidDuration(format!("Region {} must start at or after 0s", name)));
+        }
+        if region.end <= region.start {
+            return Err(AudioError::InvalidDuration(format!(
+                "Region {} ends at {:.3}s, before it starts at {:.3}s", name, region.end, region.start
+            )));
+        }
+        if region.end > total_duration {
+            return Err(AudioError::InvalidDuration(format!(
+                "Region {} ends at {:.3}s but only {:.3}s of audio is available", name, region.end, total_duration
+            )));
+        }
+        if !seen.insert(name.to_lowercase()) {
+            return Err(AudioError::ProcessingError(format!("Duplicate region name {}", name)));
+        }
+
+        names.push(name);
+    }
+
+    Ok(names)
+}
+
+/// Turn a user-supplied name into a safe `.wav` file name
+fn file_name(name: Option<&str>, index: usize) -> String {
+    let cleaned: String = name
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') { c } else { '_' })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+
+    if cleaned.is_empty() {
+        format!("region_{}.wav", index)
+    } else if cleaned.to_lowercase().ends_with(".wav") {
+        cleaned.to_string()
+    } else {
+        format!("{}.wav", cleaned)
+    }
+}
+
+/// Parse seconds, `m:ss(.fff)` or `h:mm:ss(.fff)`
+pub fn parse_time(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .split(':')
+        .try_fold((0.0, 0), |(total, parts), part| {
+            let part: f64 = part.parse().ok()?;
+            (part >= 0.0 && parts < 3).then_some((total * 60.0 + part, parts + 1))
+        })
+        .map(|(total, _)| total)
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Time {
+        Seconds(f64),
+        Timestamp(String),
+    }
+
+    match Time::deserialize(deserializer)? {
+        Time::Seconds(seconds) => Ok(seconds),
+        Time::Timestamp(value) => parse_time(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid time '{}', expected seconds or m:ss", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("92.5"), Some(92.5));
+        assert_eq!(parse_time("1:32"), Some(92.0));
+        assert_eq!(parse_time("1:01:32.5"), Some(3692.5));
+        assert_eq!(parse_time("1:-2"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("abc"), None);
+    }
+
+    #[test]
+    fn test_file_names_are_sanitized() {
+        assert_eq!(file_name(Some("../../etc/passwd"), 0), "_.._etc_passwd.wav");
+        assert_eq!(file_name(Some("Kick.WAV"), 0), "Kick.WAV");
+        assert_eq!(file_name(Some("  "), 3), "region_3.wav");
+        assert_eq!(file_name(None, 1), "region_1.wav");
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::Value;
+
+use crate::errors::{AudioError, AudioResult};
+use super::effects::{FadeProcessor, ReverseProcessor};
+use super::normalize::NormalizeProcessor;
+use super::pipeline::PipelineProcessor;
+use super::silence::SilenceProcessor;
+use super::splice::SpliceProcessor;
+use super::{AudioProcessor, ProcessorConfig};
+
+/// A processor the service can run, under the `type` its configs are tagged with
+pub struct Registration {
+    pub name: &'static str,
+    create: fn() -> Box<dyn AudioProcessor>,
+}
+
+/// Every processor, keyed by config type. Adding a processor here is enough for
+/// `processor_for`, pipelines, jobs and `/api/v1/audio/{processor}` to run it.
+const REGISTRY: &[Registration] = &[
+    Registration { name: "splice", create: || Box::new(SpliceProcessor::new()) },
+    Registration { name: "regions", create: || Box::new(SpliceProcessor::new()) },
+    Registration { name: "normalize", create: || Box::new(NormalizeProcessor::new()) },
+    Registration { name: "slice_by_silence", create: || Box::new(SilenceProcessor::new()) },
+    Registration { name: "reverse", create: || Box::new(ReverseProcessor::new()) },
+    Registration { name: "fade", create: || Box::new(FadeProcessor::new()) },
+    Registration { name: "pipeline", create: || Box::new(PipelineProcessor::new()) },
+];
+
+/// The registration for config type `name`, failing with `AudioError::FileNotFound` if there is none
+pub fn lookup(name: &str) -> AudioResult<&'static Registration> {
+    REGISTRY.iter()
+        .find(|registration| registration.name == name)
+        .ok_or_else(|| AudioError::FileNotFound(format!("No processor named {}", name)))
+}
+
+impl Registration {
+    pub fn create(&self) -> Box<dyn AudioProcessor> {
+        (self.create)()
+    }
+
+    /// Parse a config for this processor from JSON, which may leave out its `type`
+    pub fn config_from_json(&self, mut value: Value) -> AudioResult<ProcessorConfig> {
+        let Some(fields) = value.as_object_mut() else {
+            return Err(AudioError::ProcessingError("Invalid config: expected a JSON object".to_string()));
+        };
+        match fields.get("type").and_then(Value::as_str) {
+            None => {
+                fields.insert("type".to_string(), Value::from(self.name));
+            },
+            Some(name) if name == self.name => {},
+            Some(name) => {
+                return Err(AudioError::ProcessingError(format!("Invalid config: a {} config was sent to the {} processor", name, self.name)));
+            },
+        }
+
+        serde_json::from_value(value).map_err(|e| AudioError::ProcessingError(format!("Invalid config: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::dsp::fade::Fade;
+    use crate::processors::{processor_for, SilenceConfig, SpliceConfig};
+
+    #[test]
+    fn test_configs_are_parsed_for_the_named_processor() {
+        let reverse = lookup("reverse").unwrap().config_from_json(json!({})).unwrap();
+        assert!(matches!(reverse, ProcessorConfig::Reverse));
+
+        let splice = lookup("splice").unwrap();
+        let config = splice.config_from_json(json!({"duration": 1.0, "count": 2, "reverse": false})).unwrap();
+        assert_eq!(config.type_name(), "splice");
+        assert_eq!(splice.create().processor_type(), "splice");
+
+        assert!(splice.config_from_json(json!({"type": "reverse"})).is_err());
+        assert!(splice.config_from_json(json!([1, 2])).is_err());
+        assert!(matches!(lookup("reverb"), Err(AudioError::FileNotFound(_))));
+
+        for registration in REGISTRY {
+            assert_eq!(lookup(registration.name).unwrap().name, registration.name);
+        }
+    }
+
+    #[test]
+    fn test_every_config_type_has_a_processor() {
+        let configs = [
+            ProcessorConfig::Splice(SpliceConfig::new(1.0, 1)),
+            ProcessorConfig::Regions { regions: Vec::new(), output_format: None, fade: None },
+            ProcessorConfig::Normalize { target_level: 1.0, output_format: None, target_lufs: None, true_peak: false, limiter: None, splice: None },
+            ProcessorConfig::SliceBySilence(SilenceConfig::default()),
+            ProcessorConfig::Reverse,
+            ProcessorConfig::Fade(Fade::default()),
+            ProcessorConfig::Pipeline { stages: Vec::new(), output_format: None },
+        ];
+        for config in &configs {
+            // No wildcard arm, so a new variant does not compile until it is listed above
+            match config {
+                ProcessorConfig::Splice(_) | ProcessorConfig::Regions { .. } | ProcessorConfig::Normalize { .. }
+                | ProcessorConfig::SliceBySilence(_) | ProcessorConfig::Reverse | ProcessorConfig::Fade(_)
+                | ProcessorConfig::Pipeline { .. } => {},
+            }
+            assert_eq!(lookup(config.type_name()).unwrap().name, config.type_name());
+            assert!(processor_for(config).is_ok());
+        }
+        assert_eq!(REGISTRY.len(), configs.len());
+    }
+}
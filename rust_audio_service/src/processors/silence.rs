@@ -0,0 +1,252 @@
+use std::ops::Range;
+use std::time::Instant;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{AudioBuffer, PcmFormat};
+use crate::dsp::fade::Fade;
+use crate::dsp::silence::sounding_ranges;
+use crate::errors::{AudioError, AudioResult};
+use super::placement::MAX_CHOP_PIECES;
+use super::{Clip, AudioProcessor, ProcessorConfig, ProcessedAudio, ProcessingMetadata, Segment};
+
+/// Length of the blocks the level is measured over when looking for silence
+const LEVEL_BLOCK_MS: f64 = 10.0;
+
+/// Parameters for splitting a track at its silences; every field is optional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilenceConfig {
+    pub threshold_db: f64,  // Audio below this RMS level in dBFS counts as silence
+    pub min_silence_ms: f64,  // Shorter gaps don't split a segment, e.g. pauses between words
+    pub min_segment_ms: f64,  // Shorter segments are dropped, e.g. clicks and breaths
+    pub max_segment_seconds: Option<f64>,  // Longer segments are cut into equal pieces; no limit if unset
+    pub padding_ms: f64,  // Audio kept before and after each segment so tails aren't clipped
+    pub output_format: Option<PcmFormat>,  // Defaults to the input's sample format
+    pub fade: Option<Fade>,  // Applied to every segment after padding
+}
+
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: -50.0,
+            min_silence_ms: 250.0,
+            min_segment_ms: 100.0,
+            max_segment_seconds: None,
+            padding_ms: 20.0,
+            output_format: None,
+            fade: None,
+        }
+    }
+}
+
+pub struct SilenceProcessor;
+
+impl SilenceProcessor {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Frame ranges to export, after dropping short segments, padding and splitting long ones.
+    ///
+    /// Fails if there would be more than `MAX_CHOP_PIECES` of them.
+    fn plan_segments(audio: &AudioBuffer, config: &SilenceConfig) -> AudioResult<Vec<Range<usize>>> {
+        let block = audio.frames_for(LEVEL_BLOCK_MS / 1000.0);
+        let min_silence = audio.frames_for(config.min_silence_ms / 1000.0);
+        let min_segment = audio.frames_for(config.min_segment_ms / 1000.0);
+        let padding = audio.frames_for(config.padding_ms / 1000.0);
+
+        let ranges: Vec<Range<usize>> = sounding_ranges(audio, config.threshold_db, block, min_silence)
+            .into_iter()
+            .filter(|range| range.len() >= min_segment)
+            .collect();
+
+        // Padding never reaches more than halfway into the gap to a neighbouring segment
+        let padded: Vec<Range<usize>> = ranges.iter().enumerate().map(|(i, range)| {
+            let before = i.checked_sub(1).map_or(range.start, |prev| (range.start - ranges[prev].end) / 2);
+            let after = ranges.get(i + 1).map_or(audio.frame_count() - range.end, |next| (next.start - range.end) / 2);
+            range.start - padding.min(before)..range.end + padding.min(after)
+        }).collect();
+
+        // Count the pieces before cutting them, as a tiny max_segment_seconds would make one per frame
+        let max_frames = config.max_segment_seconds.map(|seconds| audio.frames_for(seconds).max(1));
+        let pieces: usize = padded
+            .iter()
+            .map(|range| max_frames.map_or(1, |max_frames| range.len().div_ceil(max_frames).max(1)))
+            .sum();
+        if pieces > MAX_CHOP_PIECES {
+            return Err(AudioError::ProcessingError(format!(
+                "Slicing would cut {} segments, at most {} are allowed; raise max_segment_seconds or min_silence_ms", pieces, MAX_CHOP_PIECES
+            )));
+        }
+
+        Ok(padded
+            .into_iter()
+            .flat_map(|range| match max_frames {
+                Some(max_frames) if range.len() > max_frames => {
+                    let pieces = range.len().div_ceil(max_frames);
+                    let piece = range.len().div_ceil(pieces);
+                    (range.start..range.end)
+                        .step_by(piece)
+                        .map(|start| start..(start + piece).min(range.end))
+                        .collect()
+                },
+                _ => vec![range],
+            })
+            .collect())
+    }
+
+    /// Cut `audio` into its segments, returning each with its file name and its span of the source
+    fn segments(audio: &AudioBuffer, config: &SilenceConfig) -> AudioResult<(Vec<Clip>, Vec<Segment>)> {
+        info!("Processing slice by silence - Threshold: {} dB, Min silence: {} ms, Min segment: {} ms, Max segment: {:?} s, Padding: {} ms",
+              config.threshold_db, config.min_silence_ms, config.min_segment_ms, config.max_segment_seconds, config.padding_ms);
+
+        let ranges = Self::plan_segments(audio, config)?;
+        if ranges.is_empty() {
+            return Err(AudioError::ProcessingError(format!(
+                "No segments found above {:.1} dBFS; lower threshold_db or min_segment_ms", config.threshold_db
+            )));
+        }
+        info!("Found {} segments", ranges.len());
+
+        let mut clips = Vec::new();
+        let mut segments = Vec::new();
+        let seconds = |frame: usize| frame as f64 / audio.sample_rate as f64;
+
+        for (i, range) in ranges.into_iter().enumerate() {
+            let file_name = format!("segment_{}.wav", i);
+            let mut segment = audio.slice_frames(range.start, range.len());
+
+            if let Some(fade) = config.fade {
+                fade.apply(&mut segment);
+            }
+
+            clips.push((file_name.clone(), segment));
+            segments.push(Segment {
+                file: file_name,
+                start: seconds(range.start),
+                end: seconds(range.end),
+            });
+        }
+
+        Ok((clips, segments))
+    }
+}
+
+impl AudioProcessor for SilenceProcessor {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
+        let start_time = Instant::now();
+
+        self.validate_config(config)?;
+        let silence_config = match config {
+            ProcessorConfig::SliceBySilence(silence_config) => silence_config,
+            _ => return Err(AudioError::ProcessingError("Invalid config for SilenceProcessor".to_string())),
+        };
+
+        let (clips, segments) = Self::segments(audio, silence_config)?;
+
+        Ok(ProcessedAudio {
+            clips,
+            metadata: ProcessingMetadata {
+                processing_time_ms: start_time.elapsed().as_millis() as u64,
+                segments,
+                ..ProcessingMetadata::new(self.processor_type(), audio)
+            },
+        })
+    }
+
+    fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
+        match config {
+            ProcessorConfig::SliceBySilence(silence_config) => {
+                if !silence_config.threshold_db.is_finite() {
+                    return Err(AudioError::ProcessingError("threshold_db must be a finite number of dBFS".to_string()));
+                }
+                let lengths = [silence_config.min_silence_ms, silence_config.min_segment_ms, silence_config.padding_ms];
+                if lengths.iter().any(|&ms| !ms.is_finite() || ms < 0.0) {
+                    return Err(AudioError::InvalidDuration(
+                        "min_silence_ms, min_segment_ms and padding_ms must be zero or positive".to_string()
+                    ));
+                }
+                if matches!(silence_config.max_segment_seconds, Some(seconds) if !seconds.is_finite() || seconds <= 0.0) {
+                    return Err(AudioError::InvalidDuration("max_segment_seconds must be positive".to_string()));
+                }
+                silence_config.fade.map_or(Ok(()), |fade| fade.validate())
+            },
+            _ => Err(AudioError::ProcessingError("Invalid config for SilenceProcessor".to_string())),
+        }
+    }
+
+    fn processor_type(&self) -> &'static str {
+        "slice_by_silence"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioSource, DecodeOptions};
+    use crate::processors::ProcessingResult;
+    use crate::workspace::Workspace;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Mono 0.5-amplitude sound for each `(start, end)` span in seconds, silence elsewhere
+    fn write_phrases(workspace: &Workspace, phrases: &[(f64, f64)], duration: f64) {
+        let frames = (SAMPLE_RATE as f64 * duration) as usize;
+        let audio = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames)
+                .map(|frame| {
+                    let now = frame as f64 / SAMPLE_RATE as f64;
+                    if phrases.iter().any(|&(start, end)| now >= start && now < end) { 0.5 } else { 0.0 }
+                })
+                .collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+    }
+
+    fn slice(workspace: &Workspace, config: SilenceConfig) -> AudioResult<ProcessingResult> {
+        SilenceProcessor::new().process(&workspace.input_path(), workspace, &ProcessorConfig::SliceBySilence(config), DecodeOptions::default())
+    }
+
+    #[test]
+    fn test_splits_at_long_gaps_and_pads() {
+        let workspace = Workspace::create().unwrap();
+        // A 0.1s pause inside the first phrase, a 0.02s click and a long second phrase
+        write_phrases(&workspace, &[(0.5, 1.0), (1.1, 1.5), (2.0, 2.02), (2.5, 4.5)], 5.0);
+
+        let config = SilenceConfig { max_segment_seconds: Some(1.2), ..Default::default() };
+        let result = slice(&workspace, config).unwrap();
+
+        let spans: Vec<(f64, f64)> = result.metadata.segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(spans, vec![(0.48, 1.52), (2.48, 3.5), (3.5, 4.52)]);
+
+        let names: Vec<_> = result.files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["segment_0.wav", "segment_1.wav", "segment_2.wav"]);
+        let first = AudioSource::open(&result.files[0]).unwrap().decode().unwrap();
+        assert_eq!(first.frame_count(), (1.04 * SAMPLE_RATE as f64).round() as usize);
+    }
+
+    #[test]
+    fn test_silent_track_is_rejected() {
+        let workspace = Workspace::create().unwrap();
+        write_phrases(&workspace, &[], 1.0);
+
+        let result = slice(&workspace, SilenceConfig::default());
+        assert!(matches!(result, Err(AudioError::ProcessingError(_))));
+    }
+
+    #[test]
+    fn test_tiny_max_segment_is_rejected_before_cutting() {
+        let workspace = Workspace::create().unwrap();
+        // 2 seconds of sound at 8 kHz is 16 000 one-frame pieces
+        write_phrases(&workspace, &[(0.5, 2.5)], 3.0);
+
+        let config = SilenceConfig { max_segment_seconds: Some(1e-9), ..Default::default() };
+        let result = slice(&workspace, config);
+        assert!(matches!(result, Err(AudioError::ProcessingError(ref message)) if message.contains("at most 10000")));
+        assert_eq!(std::fs::read_dir(workspace.output_dir()).unwrap().count(), 0);
+    }
+}
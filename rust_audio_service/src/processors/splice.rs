@@ -1,23 +1,39 @@
-use std::path::PathBuf;
 use std::time::Instant;
-use hound::{WavReader, WavWriter};
-use rand::Rng;
 use log::info;
 
+use crate::audio::AudioBuffer;
+use crate::dsp::fade::Fade;
+use crate::dsp::level::rms_db;
+use crate::dsp::onset::detect_onsets;
+use crate::dsp::tempo::{detect_beat_grid, BeatGrid};
+use crate::dsp::zero_crossing::nearest_zero_crossing;
 use crate::errors::{AudioError, AudioResult};
-use super::{AudioProcessor, ProcessorConfig, ProcessingResult, ProcessingMetadata};
+use super::{splice_rng, Clip, AudioProcessor, ProcessorConfig, ProcessedAudio, ProcessingMetadata, SpliceConfig, Clamp, Region, SpliceBoundary, TimeWindow, Placement};
+use super::regions::validate_regions;
+
+/// Redraws allowed per splice when `min_rms_db` is set and `max_redraws` is not
+const DEFAULT_MAX_REDRAWS: u32 = 100;
+/// Tempos accepted for `bpm`; beat phase estimation slows to a crawl on very short beats
+const BPM_RANGE: std::ops::RangeInclusive<f64> = 20.0..=400.0;
 
 pub struct SpliceProcessor;
 
+/// Splices cut by one of the splice modes, with their file names, plus what to report about them
+#[derive(Default)]
+struct SpliceOutput {
+    splices: Vec<Clip>,
+    seed: Option<u64>,
+    window: Option<TimeWindow>,
+    boundaries: Vec<SpliceBoundary>,
+    onsets_detected: Option<usize>,
+    bpm: Option<f64>,
+}
+
 impl SpliceProcessor {
     pub fn new() -> Self {
         Self
     }
 
-    fn reverse_samples(samples: &mut [i16]) {
-        samples.reverse();
-    }
-
     fn validate_splice_params(splice_duration: f64, splice_count: i32) -> AudioResult<()> {
         if splice_duration <= 0.0 {
             return Err(AudioError::InvalidDuration("splice_duration must be positive".to_string()));
@@ -27,78 +43,347 @@ impl SpliceProcessor {
         }
         Ok(())
     }
-}
 
-impl AudioProcessor for SpliceProcessor {
-    fn process(&self, input_path: &str, output_dir: &str, config: &ProcessorConfig) -> AudioResult<ProcessingResult> {
-        let start_time = Instant::now();
-        
-        let (duration, count, reverse) = match config {
-            ProcessorConfig::Splice { duration, count, reverse } => (*duration, *count, *reverse),
-            _ => return Err(AudioError::ProcessingError("Invalid config for SpliceProcessor".to_string())),
+    fn validate_beat_params(config: &SpliceConfig, beats: u32) -> AudioResult<()> {
+        if config.count < 1 {
+            return Err(AudioError::InvalidSpliceCount("splice count must be >= 1".to_string()));
+        }
+        if config.beats_per_bar() == 0 {
+            return Err(AudioError::InvalidDuration("beats_per_bar must be at least 1".to_string()));
+        }
+        if beats == 0 {
+            return Err(AudioError::InvalidDuration("bars and beats must add up to at least one beat".to_string()));
+        }
+        if matches!(config.bpm, Some(bpm) if !BPM_RANGE.contains(&bpm)) {
+            return Err(AudioError::InvalidDuration(format!(
+                "bpm must be between {} and {}", BPM_RANGE.start(), BPM_RANGE.end()
+            )));
+        }
+        if matches!(config.placement, Placement::Onsets(_)) {
+            return Err(AudioError::ProcessingError("onset placement cannot be combined with bars or beats".to_string()));
+        }
+        if config.min_rms_db.is_some() {
+            return Err(AudioError::ProcessingError("min_rms_db cannot be combined with bars or beats".to_string()));
+        }
+        Ok(())
+    }
+
+    fn validate_window(window_start: Option<f64>, window_end: Option<f64>) -> AudioResult<()> {
+        for value in [window_start, window_end].into_iter().flatten() {
+            if !value.is_finite() || value < 0.0 {
+                return Err(AudioError::InvalidDuration("window_start and window_end must be zero or positive".to_string()));
+            }
+        }
+        if let (Some(start), Some(end)) = (window_start, window_end) {
+            if end <= start {
+                return Err(AudioError::InvalidDuration("window_end must be after window_start".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of frames to cut per splice.
+    ///
+    /// Checked against the decoded audio in the window rather than the file
+    /// header. A splice longer than the window is an error unless a clamp mode is
+    /// set, in which case the whole window is used (and `Clamp::Pad` later fills in
+    /// the rest).
+    fn fit_splice_length(audio: &AudioBuffer, window_frames: usize, duration: f64, clamp: Option<Clamp>) -> AudioResult<usize> {
+        let requested = audio.frames_for(duration);
+
+        if requested == 0 {
+            return Err(AudioError::InvalidDuration("splice_duration is shorter than a single sample".to_string()));
+        }
+        if requested <= window_frames {
+            return Ok(requested);
+        }
+
+        match clamp {
+            Some(_) if window_frames > 0 => Ok(window_frames),
+            _ => Err(AudioError::SpliceTooLong {
+                requested: duration,
+                available: window_frames as f64 / audio.sample_rate as f64,
+            }),
+        }
+    }
+
+    /// Frame range splices may be taken from, with the window end clamped to the track
+    fn resolve_window(audio: &AudioBuffer, window_start: Option<f64>, window_end: Option<f64>) -> AudioResult<(usize, usize)> {
+        let total_frames = audio.frame_count();
+        let start = window_start.map_or(0, |seconds| audio.frames_for(seconds));
+        let end = window_end.map_or(total_frames, |seconds| audio.frames_for(seconds).min(total_frames));
+
+        if start >= total_frames {
+            return Err(AudioError::InvalidDuration(format!(
+                "window_start {:.3}s is past the end of the {:.3}s track", window_start.unwrap_or(0.0), audio.duration()
+            )));
+        }
+        if end <= start {
+            return Err(AudioError::InvalidDuration("window_end must be after window_start".to_string()));
+        }
+
+        Ok((start, end))
+    }
+
+    /// Window-relative start frames on downbeats of `grid` for splices of `beats` beats.
+    ///
+    /// The placement is planned in whole bars from the first downbeat in the
+    /// window, so every splice starts on a downbeat and a minimum gap is rounded
+    /// up to whole bars.
+    fn plan_on_grid<R: rand::Rng>(
+        grid: &BeatGrid,
+        window: (usize, usize),
+        beats: u32,
+        count: usize,
+        placement: Placement,
+        rng: &mut R,
+    ) -> AudioResult<Vec<usize>> {
+        let (window_start, window_end) = window;
+        let bar_seconds = grid.bar_frames() / grid.sample_rate as f64;
+        let first_bar = grid.first_bar_from(window_start);
+        let loop_bars = beats.div_ceil(grid.beats_per_bar) as usize;
+
+        // Whole bars between the first downbeat in the window and its end
+        let total_bars = (first_bar..)
+            .take_while(|&bar| grid.downbeat(bar + 1) <= window_end)
+            .count();
+        if total_bars < loop_bars {
+            return Err(AudioError::SpliceTooLong {
+                requested: grid.frames_for_beats(beats) as f64 / grid.sample_rate as f64,
+                available: total_bars as f64 * bar_seconds,
+            });
+        }
+
+        let placement = match placement {
+            Placement::MinGapSeconds(gap) => Placement::MinGapSeconds((gap / bar_seconds).ceil()),
+            other => other,
         };
+        let bars = placement.plan_starts(total_bars, loop_bars, count, 1, rng)?;
 
-        self.validate_config(config)?;
-        
-        std::fs::create_dir_all(output_dir).map_err(|e| AudioError::IoError(e))?;
-        let mut reader = WavReader::open(input_path).map_err(|e| AudioError::WavError(e))?;
-        let spec = reader.spec();
-        let total_duration = reader.duration() as f64 / spec.sample_rate as f64;
+        Ok(bars.into_iter().map(|bar| grid.downbeat(first_bar + bar) - window_start).collect())
+    }
 
-        info!("Processing splice - Duration: {}, Count: {}, Reverse: {}", duration, count, reverse);
+    /// Cut `count` splices at positions chosen by the configured placement
+    fn random_splices(audio: &AudioBuffer, config: &SpliceConfig) -> AudioResult<SpliceOutput> {
+        let SpliceConfig {
+            duration, count, reverse, seed, placement, clamp, window_start, window_end, zero_crossing_radius_ms, fade,
+            min_rms_db, max_redraws, bpm, ..
+        } = *config;
 
-        let mut rng = rand::thread_rng();
-        let mut splice_files = Vec::new();
+        let (window_start_frame, window_end_frame) = Self::resolve_window(audio, window_start, window_end)?;
+        let window_frames = window_end_frame - window_start_frame;
+        let window = TimeWindow {
+            start: window_start_frame as f64 / audio.sample_rate as f64,
+            end: window_end_frame as f64 / audio.sample_rate as f64,
+        };
 
-        for i in 0..count {
-            let start_time_splice = rng.gen_range(0.0..total_duration - duration);
-            let start_sample = (start_time_splice * spec.sample_rate as f64) as u32;
-            let splice_samples = (duration * spec.sample_rate as f64) as u32;
+        let beat_length = config.beat_length();
+        // Splices measured in beats follow the track's beat grid; clamp does not apply to them
+        let beat_grid = match beat_length {
+            Some(beats) => {
+                let grid = detect_beat_grid(audio, config.beats_per_bar(), bpm).ok_or_else(|| {
+                    AudioError::ProcessingError("Could not detect a beat in the track; set bpm to give the tempo".to_string())
+                })?;
+                Some((grid, beats))
+            },
+            None => None,
+        };
 
-            let output_path = PathBuf::from(output_dir).join(format!("splice_{}.wav", i));
-            let mut writer = WavWriter::create(&output_path, spec).map_err(|e| AudioError::WavError(e))?;
-            reader.seek(start_sample).map_err(|e| AudioError::IoError(e))?;
-            
-            let mut samples = Vec::with_capacity(splice_samples as usize);
-            for _ in 0..splice_samples {
-                if let Some(sample) = reader.samples::<i16>().next() {
-                    let sample_value = sample.map_err(|e| AudioError::WavError(e))?;
-                    samples.push(sample_value);
-                } else {
-                    break;
+        let (requested_frames, splice_frames) = match beat_grid {
+            Some((grid, beats)) => (grid.frames_for_beats(beats), grid.frames_for_beats(beats)),
+            None => (audio.frames_for(duration), Self::fit_splice_length(audio, window_frames, duration, clamp)?),
+        };
+        let (mut rng, seed) = splice_rng(seed);
+        let snap_radius = zero_crossing_radius_ms.map(|ms| audio.frames_for(ms / 1000.0));
+
+        info!("Processing splice - Duration: {}, Beats: {:?}, Count: {}, Reverse: {}, Placement: {:?}, Clamp: {:?}, Window: {:.3}-{:.3}s, Snap: {:?}, Seed: {}",
+              duration, beat_length, count, reverse, placement, clamp, window.start, window.end, snap_radius, seed);
+
+        let mut onsets_detected = None;
+        let mut starts = if let Some((grid, beats)) = beat_grid {
+            info!("Detected {:.2} BPM, first downbeat at {:.3}s", grid.bpm, grid.first_downbeat / audio.sample_rate as f64);
+            Self::plan_on_grid(&grid, (window_start_frame, window_end_frame), beats, count as usize, placement, &mut rng)?
+        } else {
+            match placement {
+                Placement::Onsets(settings) => {
+                    let min_interval = audio.frames_for(settings.min_interval_ms / 1000.0);
+                    // Only onsets inside the window with room for a whole splice after them
+                    let onsets: Vec<usize> = detect_onsets(audio, settings.sensitivity, min_interval)
+                        .into_iter()
+                        .filter(|&onset| onset >= window_start_frame && onset + splice_frames <= window_end_frame)
+                        .map(|onset| onset - window_start_frame)
+                        .collect();
+                    info!("Detected {} usable onsets", onsets.len());
+                    onsets_detected = Some(onsets.len());
+                    Placement::pick_onsets(&onsets, count as usize, &mut rng)?
+                },
+                _ => placement.plan_starts(
+                    window_frames,
+                    splice_frames,
+                    count as usize,
+                    audio.sample_rate,
+                    &mut rng,
+                )?,
+            }
+        };
+
+        if let Some(threshold_db) = min_rms_db {
+            // Keep drawing new positions for quiet splices until each is loud enough
+            let max_redraws = max_redraws.unwrap_or(DEFAULT_MAX_REDRAWS);
+            for i in 0..starts.len() {
+                let mut redraws = 0;
+                while rms_db(audio, window_start_frame + starts[i], splice_frames) < threshold_db {
+                    if redraws == max_redraws {
+                        return Err(AudioError::TooQuiet { threshold_db, attempts: redraws + 1 });
+                    }
+                    redraws += 1;
+                    if let Some(start) = placement.redraw_start(&starts, i, window_frames, splice_frames, audio.sample_rate, &mut rng) {
+                        starts[i] = start;
+                    }
                 }
             }
+        }
+
+        let mut splices = Vec::new();
+        let mut boundaries = Vec::new();
+        let seconds = |frame: usize| frame as f64 / audio.sample_rate as f64;
+
+        for (i, start_frame) in starts.into_iter().enumerate() {
+            let file_name = format!("splice_{}.wav", i);
+            let requested_start = window_start_frame + start_frame;
+            let requested_end = (requested_start + splice_frames).min(window_end_frame);
+            let (mut start, mut end) = (requested_start, requested_end);
+
+            if let Some(radius) = snap_radius {
+                // Snapping never leaves the window or lets a splice collapse to nothing
+                start = nearest_zero_crossing(audio, start, radius, window_start_frame, end - 1).unwrap_or(start);
+                end = nearest_zero_crossing(audio, end, radius, start + 1, window_end_frame).unwrap_or(end);
+                boundaries.push(SpliceBoundary {
+                    file: file_name.clone(),
+                    requested_start: seconds(requested_start),
+                    requested_end: seconds(requested_end),
+                    start: seconds(start),
+                    end: seconds(end),
+                });
+            }
+
+            let mut splice = audio.slice_frames(start, end - start);
             
             if reverse {
-                Self::reverse_samples(&mut samples);
+                splice.reverse_frames();
             }
-            
-            for sample in samples {
-                writer.write_sample(sample).map_err(|e| AudioError::WavError(e))?;
+
+            if let Some(fade) = fade {
+                fade.apply(&mut splice);
+            }
+
+            if clamp == Some(Clamp::Pad) {
+                splice.pad_to_frames(requested_frames);
+            }
+
+            splices.push((file_name, splice));
+        }
+
+        Ok(SpliceOutput {
+            splices,
+            seed: Some(seed),
+            window: Some(window),
+            boundaries,
+            onsets_detected,
+            bpm: beat_grid.map(|(grid, _)| grid.bpm),
+        })
+    }
+
+    /// Cut each region exactly, naming the files after the regions
+    fn region_splices(audio: &AudioBuffer, regions: &[Region], fade: Option<Fade>) -> AudioResult<SpliceOutput> {
+        let names = validate_regions(regions, audio.duration())?;
+
+        info!("Processing regions - Count: {}", regions.len());
+
+        let mut splices = Vec::new();
+
+        for (region, name) in regions.iter().zip(names) {
+            let start_frame = audio.frames_for(region.start);
+            let end_frame = audio.frames_for(region.end);
+            let mut splice = audio.slice_frames(start_frame, end_frame.saturating_sub(start_frame));
+
+            if region.reverse {
+                splice.reverse_frames();
+            }
+
+            if let Some(fade) = fade {
+                fade.apply(&mut splice);
             }
 
-            splice_files.push(output_path);
+            splices.push((name, splice));
         }
 
-        let processing_time = start_time.elapsed();
-        
-        Ok(ProcessingResult {
-            files: splice_files,
+        Ok(SpliceOutput {
+            splices,
+            ..Default::default()
+        })
+    }
+
+    /// Cut splices or regions from `audio` in memory, without writing anything
+    fn splices(audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<SpliceOutput> {
+        match config {
+            ProcessorConfig::Splice(splice_config) => Self::random_splices(audio, splice_config),
+            ProcessorConfig::Regions { regions, fade, .. } => Self::region_splices(audio, regions, *fade),
+            _ => Err(AudioError::ProcessingError("Invalid config for SpliceProcessor".to_string())),
+        }
+    }
+}
+
+impl AudioProcessor for SpliceProcessor {
+    fn process_buffer(&self, audio: &AudioBuffer, config: &ProcessorConfig) -> AudioResult<ProcessedAudio> {
+        let start_time = Instant::now();
+
+        self.validate_config(config)?;
+        let output = Self::splices(audio, config)?;
+
+        Ok(ProcessedAudio {
+            clips: output.splices,
             metadata: ProcessingMetadata {
-                processor_type: self.processor_type().to_string(),
-                input_duration: total_duration,
-                sample_rate: spec.sample_rate,
-                channels: spec.channels,
-                processing_time_ms: processing_time.as_millis() as u64,
+                processing_time_ms: start_time.elapsed().as_millis() as u64,
+                seed: output.seed,
+                window: output.window,
+                boundaries: output.boundaries,
+                onsets_detected: output.onsets_detected,
+                bpm: output.bpm,
+                ..ProcessingMetadata::new(self.processor_type(), audio)
             },
         })
     }
 
     fn validate_config(&self, config: &ProcessorConfig) -> AudioResult<()> {
         match config {
-            ProcessorConfig::Splice { duration, count, .. } => {
-                Self::validate_splice_params(*duration, *count)
+            ProcessorConfig::Splice(splice_config) => {
+                match splice_config.beat_length() {
+                    // The length comes from the beat grid, so `duration` is ignored
+                    Some(beats) => Self::validate_beat_params(splice_config, beats)?,
+                    None => Self::validate_splice_params(splice_config.duration, splice_config.count)?,
+                }
+                Self::validate_window(splice_config.window_start, splice_config.window_end)?;
+                if matches!(splice_config.zero_crossing_radius_ms, Some(ms) if !ms.is_finite() || ms < 0.0) {
+                    return Err(AudioError::InvalidDuration("zero_crossing_radius_ms must be zero or positive".to_string()));
+                }
+                splice_config.fade.map_or(Ok(()), |fade| fade.validate())?;
+                if let Some(threshold_db) = splice_config.min_rms_db {
+                    if !threshold_db.is_finite() {
+                        return Err(AudioError::ProcessingError("min_rms_db must be a finite number of dBFS".to_string()));
+                    }
+                    if !splice_config.placement.is_random() {
+                        return Err(AudioError::ProcessingError(
+                            "min_rms_db needs a random placement (random, non_overlapping or min_gap_seconds)".to_string()
+                        ));
+                    }
+                }
+                splice_config.placement.validate()
             },
+            ProcessorConfig::Regions { regions, .. } if regions.is_empty() => {
+                Err(AudioError::InvalidSpliceCount("at least one region is required".to_string()))
+            },
+            ProcessorConfig::Regions { fade, .. } => fade.map_or(Ok(()), |fade| fade.validate()),
             _ => Err(AudioError::ProcessingError("Invalid config for SpliceProcessor".to_string())),
         }
     }
@@ -106,4 +391,378 @@ impl AudioProcessor for SpliceProcessor {
     fn processor_type(&self) -> &'static str {
         "splice"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+    use actix_web::http::StatusCode;
+    use crate::audio::{AudioSource, DecodeOptions, PcmFormat};
+    use crate::processors::ProcessingResult;
+    use crate::workspace::Workspace;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Write a 3 second test file where channel `c` holds the constant value `(c + 1) / 10`
+    fn write_input(workspace: &Workspace, channels: u16) {
+        let frames = SAMPLE_RATE as usize * 3;
+        let samples = (0..frames)
+            .flat_map(|_| (0..channels).map(|channel| (channel + 1) as f32 / 10.0))
+            .collect();
+        let buffer = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels,
+            format: PcmFormat::Float32,
+            samples,
+        };
+        buffer.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+    }
+
+    fn splice_layout(channels: u16, reverse: bool) {
+        let workspace = Workspace::create().unwrap();
+        write_input(&workspace, channels);
+
+        let config = ProcessorConfig::Splice(SpliceConfig { reverse, ..SpliceConfig::new(0.5, 3) });
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        assert_eq!(result.files.len(), 3);
+        assert_eq!(result.metadata.channels, channels);
+        assert_eq!(result.metadata.input_duration, 3.0);
+
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(splice.channels, channels);
+            assert_eq!(splice.frame_count(), SAMPLE_RATE as usize / 2);
+            assert_eq!(splice.duration(), 0.5);
+            for frame in splice.samples.chunks_exact(channels as usize) {
+                for (channel, &sample) in frame.iter().enumerate() {
+                    assert_eq!(sample, (channel + 1) as f32 / 10.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_splice_mono() {
+        splice_layout(1, false);
+    }
+
+    #[test]
+    fn test_splice_stereo() {
+        splice_layout(2, false);
+    }
+
+    #[test]
+    fn test_splice_multichannel() {
+        splice_layout(6, false);
+    }
+
+    #[test]
+    fn test_reversed_splice_stereo_keeps_channels() {
+        splice_layout(2, true);
+    }
+
+    #[test]
+    fn test_reversed_splice_multichannel_keeps_channels() {
+        splice_layout(6, true);
+    }
+
+    #[test]
+    fn test_regions_are_cut_exactly_and_named() {
+        let workspace = Workspace::create().unwrap();
+        write_input(&workspace, 2);
+
+        let config: ProcessorConfig = serde_json::from_str(r#"{
+            "type": "regions",
+            "regions": [
+                {"start": 0.25, "end": 1.0, "name": "intro"},
+                {"start": "0:01.5", "end": "0:03", "reverse": true},
+                {"start": 2, "end": 2.125, "name": "hit.wav"}
+            ]
+        }"#).unwrap();
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        let names: Vec<_> = result.files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["intro.wav", "region_1.wav", "hit.wav"]);
+        assert_eq!(result.metadata.seed, None);
+
+        let frames: Vec<_> = result.files.iter()
+            .map(|f| AudioSource::open(f).unwrap().decode().unwrap().frame_count())
+            .collect();
+        assert_eq!(frames, vec![6000, 12000, 1000]);
+    }
+
+    #[test]
+    fn test_region_past_end_is_rejected() {
+        let workspace = Workspace::create().unwrap();
+        write_input(&workspace, 1);
+
+        let config: ProcessorConfig = serde_json::from_str(
+            r#"{"type": "regions", "regions": [{"start": 2.5, "end": 3.5}]}"#
+        ).unwrap();
+        let result = SpliceProcessor::new().process(&workspace.input_path(), &workspace, &config, DecodeOptions::default());
+
+        assert!(matches!(result, Err(AudioError::InvalidDuration(_))));
+    }
+
+    #[test]
+    fn test_splices_stay_inside_window() {
+        let workspace = Workspace::create().unwrap();
+        // Mono ramp from 0.0 to 1.0 so each sample encodes its position in the track
+        let frames = SAMPLE_RATE as usize * 3;
+        let ramp = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames).map(|frame| frame as f32 / frames as f32).collect(),
+        };
+        ramp.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let config = ProcessorConfig::Splice(SpliceConfig {
+            seed: Some(7),
+            window_start: Some(1.0),
+            window_end: Some(2.0),
+            ..SpliceConfig::new(0.25, 20)
+        });
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        assert_eq!(result.metadata.window, Some(TimeWindow { start: 1.0, end: 2.0 }));
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            let start_seconds = splice.samples[0] as f64 * 3.0;
+            assert!(start_seconds >= 1.0 - 1e-4, "splice starts at {}s", start_seconds);
+            assert!(start_seconds + 0.25 <= 2.0 + 1e-4, "splice starts at {}s", start_seconds);
+        }
+    }
+
+    #[test]
+    fn test_zero_crossing_snapping_records_boundaries() {
+        let workspace = Workspace::create().unwrap();
+        // 100 Hz sine: crosses zero every 40 frames at 8 kHz
+        let frames = SAMPLE_RATE as usize * 3;
+        let sine = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames)
+                .map(|frame| (frame as f32 * 100.0 * std::f32::consts::TAU / SAMPLE_RATE as f32).sin() * 0.8)
+                .collect(),
+        };
+        sine.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let config = ProcessorConfig::Splice(SpliceConfig { seed: Some(3), zero_crossing_radius_ms: Some(5.0), ..SpliceConfig::new(0.3, 5) });
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        assert_eq!(result.metadata.boundaries.len(), 5);
+        for (file, boundary) in result.files.iter().zip(&result.metadata.boundaries) {
+            assert_eq!(file.file_name().unwrap().to_str().unwrap(), boundary.file);
+            assert!((boundary.start - boundary.requested_start).abs() <= 0.005);
+            assert!((boundary.end - boundary.requested_end).abs() <= 0.005);
+
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(splice.frame_count(), ((boundary.end - boundary.start) * SAMPLE_RATE as f64).round() as usize);
+            // One step of the sine is about 0.063, so both edges must sit right at zero
+            assert!(splice.samples[0].abs() < 0.07);
+            assert!(splice.samples[splice.samples.len() - 1].abs() < 0.07);
+        }
+    }
+
+    /// 3 seconds of mono audio: silent for the first 2 seconds, then a constant 0.5
+    fn write_quiet_intro(workspace: &Workspace) {
+        let frames = SAMPLE_RATE as usize * 3;
+        let audio = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames).map(|frame| if frame < SAMPLE_RATE as usize * 2 { 0.0 } else { 0.5 }).collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+    }
+
+    fn quiet_intro_config(placement: Placement, min_rms_db: f64) -> ProcessorConfig {
+        ProcessorConfig::Splice(SpliceConfig {
+            seed: Some(11),
+            placement,
+            min_rms_db: Some(min_rms_db),
+            max_redraws: Some(500),
+            ..SpliceConfig::new(0.2, 4)
+        })
+    }
+
+    #[test]
+    fn test_quiet_splices_are_redrawn() {
+        for placement in [Placement::Random, Placement::NonOverlapping] {
+            let workspace = Workspace::create().unwrap();
+            write_quiet_intro(&workspace);
+
+            let result = SpliceProcessor::new()
+                .process(&workspace.input_path(), &workspace, &quiet_intro_config(placement, -20.0), DecodeOptions::default())
+                .unwrap();
+
+            assert_eq!(result.files.len(), 4);
+            for file in &result.files {
+                let splice = AudioSource::open(file).unwrap().decode().unwrap();
+                let level = rms_db(&splice, 0, splice.frame_count());
+                assert!(level >= -20.0, "{:?} kept a splice at {:.1} dB", placement, level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_silent_track_gives_up_with_too_quiet() {
+        let workspace = Workspace::create().unwrap();
+        write_quiet_intro(&workspace);
+
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &quiet_intro_config(Placement::Random, 0.0), DecodeOptions::default());
+
+        assert!(matches!(result, Err(AudioError::TooQuiet { attempts: 501, .. })));
+    }
+
+    #[test]
+    fn test_onset_placement_starts_on_hits() {
+        let workspace = Workspace::create().unwrap();
+        // Silence with a decaying 300 Hz tone starting every half second from 0.5s
+        let frames = SAMPLE_RATE as usize * 3;
+        let hits = [0.5, 1.0, 1.5, 2.0];
+        let audio = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 2,
+            format: PcmFormat::Float32,
+            samples: (0..frames)
+                .flat_map(|frame| {
+                    let now = frame as f64 / SAMPLE_RATE as f64;
+                    let sample: f64 = hits.iter()
+                        .map(|&hit| now - hit)
+                        .filter(|&t| t >= 0.0)
+                        .map(|t| 0.8 * (-t / 0.05).exp() * (std::f64::consts::TAU * 300.0 * t).sin())
+                        .sum();
+                    [sample as f32, sample as f32]
+                })
+                .collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let config: ProcessorConfig = serde_json::from_str(r#"{
+            "type": "splice", "duration": 0.2, "count": 3, "reverse": false,
+            "placement": {"onsets": {"sensitivity": 0.5}}
+        }"#).unwrap();
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        assert_eq!(result.metadata.onsets_detected, Some(4));
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            // The attack is within the first few milliseconds of every splice
+            let attack = splice.slice_frames(0, SAMPLE_RATE as usize / 200);
+            assert!(attack.samples.iter().any(|s| s.abs() > 0.5));
+        }
+    }
+
+    #[test]
+    fn test_bar_splices_start_on_downbeats() {
+        let workspace = Workspace::create().unwrap();
+        // Four bars at 120 BPM from 0.25s: a loud kick on each downbeat and softer ones on the other beats
+        let frames = SAMPLE_RATE as usize * 9;
+        let audio = AudioBuffer {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames)
+                .map(|frame| {
+                    let now = frame as f64 / SAMPLE_RATE as f64;
+                    (0..16)
+                        .map(|beat| (now - 0.25 - beat as f64 * 0.5, if beat % 4 == 0 { 0.9 } else { 0.4 }))
+                        .filter(|&(t, _)| t >= 0.0)
+                        .map(|(t, level)| level * (-t / 0.08).exp() * (std::f64::consts::TAU * 150.0 * t).sin())
+                        .sum::<f64>() as f32
+                })
+                .collect(),
+        };
+        audio.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let config: ProcessorConfig = serde_json::from_str(r#"{
+            "type": "splice", "count": 2, "reverse": false, "bars": 1, "placement": "non_overlapping"
+        }"#).unwrap();
+        let result = SpliceProcessor::new()
+            .process(&workspace.input_path(), &workspace, &config, DecodeOptions::default())
+            .unwrap();
+
+        let bpm = result.metadata.bpm.unwrap();
+        assert!((bpm - 120.0).abs() < 0.5, "detected {} BPM", bpm);
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert!(splice.frame_count().abs_diff(SAMPLE_RATE as usize * 2) < 20);
+            // Only downbeats reach above the softer kicks within the first 10 ms
+            let attack = splice.slice_frames(0, SAMPLE_RATE as usize / 100);
+            assert!(attack.samples.iter().any(|s| s.abs() > 0.6));
+        }
+    }
+
+    #[test]
+    fn test_bpm_outside_the_accepted_range_is_rejected() {
+        let processor = SpliceProcessor::new();
+        let with_bpm = |bpm| ProcessorConfig::Splice(SpliceConfig { bars: Some(1), bpm: Some(bpm), ..SpliceConfig::new(0.0, 2) });
+
+        assert!(processor.validate_config(&with_bpm(120.0)).is_ok());
+        for bpm in [0.0, 10.0, 1000.0, f64::INFINITY, f64::NAN] {
+            assert!(matches!(processor.validate_config(&with_bpm(bpm)), Err(AudioError::InvalidDuration(_))), "{} BPM", bpm);
+        }
+    }
+
+    /// Splice the 3 second mono test file into two splices of 4 seconds with `clamp`
+    fn splice_past_the_end(clamp: Option<Clamp>) -> (Workspace, AudioResult<ProcessingResult>) {
+        let workspace = Workspace::create().unwrap();
+        write_input(&workspace, 1);
+        let config = ProcessorConfig::Splice(SpliceConfig { clamp, ..SpliceConfig::new(4.0, 2) });
+        let result = SpliceProcessor::new().process(&workspace.input_path(), &workspace, &config, DecodeOptions::default());
+        (workspace, result)
+    }
+
+    #[test]
+    fn test_splice_longer_than_the_track_is_a_bad_request() {
+        let (_workspace, result) = splice_past_the_end(None);
+        let error = result.unwrap_err();
+
+        assert!(matches!(error, AudioError::SpliceTooLong { requested, available } if requested == 4.0 && available == 3.0), "{:?}", error);
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_shorten_clamp_cuts_splices_to_the_track() {
+        let (_workspace, result) = splice_past_the_end(Some(Clamp::Shorten));
+        let result = result.unwrap();
+
+        assert_eq!(result.files.len(), 2);
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(splice.duration(), 3.0);
+            assert!(splice.samples.iter().all(|&s| s == 0.1));
+        }
+    }
+
+    #[test]
+    fn test_pad_clamp_fills_the_rest_with_silence() {
+        let (_workspace, result) = splice_past_the_end(Some(Clamp::Pad));
+        let result = result.unwrap();
+
+        assert_eq!(result.files.len(), 2);
+        for file in &result.files {
+            let splice = AudioSource::open(file).unwrap().decode().unwrap();
+            assert_eq!(splice.duration(), 4.0);
+            let (audio, padding) = splice.samples.split_at(SAMPLE_RATE as usize * 3);
+            assert!(audio.iter().all(|&s| s == 0.1));
+            assert!(padding.iter().all(|&s| s == 0.0));
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use actix_multipart::Field;
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use log::info;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::audio::DecodeOptions;
+use crate::errors::{AudioError, AudioResult};
+use crate::workspace::Workspace;
+
+/// Largest upload accepted by default, in bytes
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 200 * 1024 * 1024;
+/// Longest audio decoded by default, in seconds
+const DEFAULT_MAX_DURATION_SECS: f64 = 30.0 * 60.0;
+/// How long a stored upload is kept after it was last used
+const ASSET_TTL_MINUTES: i64 = 60;
+
+/// Limits on what a client may upload
+#[derive(Debug, Clone, Copy)]
+pub struct UploadLimits {
+    pub max_bytes: u64,
+    pub max_duration_secs: f64,
+}
+
+impl UploadLimits {
+    /// Limits from `MAX_UPLOAD_BYTES` and `MAX_AUDIO_DURATION_SECS`, falling back to the defaults
+    pub fn from_env() -> Self {
+        let max_bytes = std::env::var("MAX_UPLOAD_BYTES").ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
+        let max_duration_secs = std::env::var("MAX_AUDIO_DURATION_SECS").ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|secs: &f64| *secs > 0.0)
+            .unwrap_or(DEFAULT_MAX_DURATION_SECS);
+
+        info!("Upload limits - Max bytes: {}, Max duration: {} s", max_bytes, max_duration_secs);
+        Self { max_bytes, max_duration_secs }
+    }
+
+    /// Stream the `file` field to `path`, failing with `AudioError::TooLarge` as soon as it passes `max_bytes`.
+    ///
+    /// Returns the number of bytes written. Whatever was written before the limit
+    /// was hit stays in the workspace and goes with it.
+    pub async fn save(&self, field: &mut Field, path: &Path) -> AudioResult<u64> {
+        let mut f = std::fs::File::create(path)?;
+        let mut written: u64 = 0;
+        while let Some(chunk) = field.next().await {
+            let data = chunk.map_err(|e| AudioError::ProcessingError(e.to_string()))?;
+            written += data.len() as u64;
+            if written > self.max_bytes {
+                return Err(AudioError::TooLarge(format!("upload is larger than the {} byte limit", self.max_bytes)));
+            }
+            f.write_all(&data)?;
+        }
+        Ok(written)
+    }
+
+    /// Decode limits matching these upload limits
+    pub fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions { max_duration_secs: Some(self.max_duration_secs) }
+    }
+}
+
+/// An upload kept so several requests can process it without sending it again
+#[derive(Debug, Clone, Serialize)]
+pub struct Asset {
+    pub asset_id: String,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_used: DateTime<Utc>,
+    // Holds the uploaded file as its input; removed with the last copy
+    #[serde(skip)]
+    workspace: Arc<Workspace>,
+}
+
+impl Asset {
+    /// Make the asset's audio the input of `workspace`, linking it where possible instead of copying
+    pub fn link_into(&self, workspace: &Workspace) -> AudioResult<()> {
+        let (source, input) = (self.workspace.input_path(), workspace.input_path());
+        if std::fs::hard_link(&source, &input).is_err() {
+            std::fs::copy(&source, &input)?;
+        }
+        Ok(())
+    }
+}
+
+/// Uploads stored by `/api/v1/uploads`, looked up by asset id
+pub struct UploadStore {
+    assets: Mutex<HashMap<String, Asset>>,
+}
+
+impl UploadStore {
+    pub fn new() -> Self {
+        Self { assets: Mutex::new(HashMap::new()) }
+    }
+
+    /// The assets, after dropping those unused for longer than the TTL along with their files
+    fn live_assets(&self) -> MutexGuard<'_, HashMap<String, Asset>> {
+        let mut assets = self.assets.lock().unwrap();
+        let expiry = Utc::now() - Duration::minutes(ASSET_TTL_MINUTES);
+        assets.retain(|_, asset| asset.last_used > expiry);
+        assets
+    }
+
+    /// Keep the upload in `workspace` as a new asset
+    pub fn insert(&self, workspace: Workspace, size_bytes: u64) -> Asset {
+        let now = Utc::now();
+        let asset = Asset {
+            asset_id: Uuid::new_v4().to_string(),
+            size_bytes,
+            created_at: now,
+            last_used: now,
+            workspace: Arc::new(workspace),
+        };
+
+        self.live_assets().insert(asset.asset_id.clone(), asset.clone());
+        asset
+    }
+
+    /// The asset with `asset_id`, if it is still stored; using it keeps it for another TTL
+    pub fn get(&self, asset_id: &str) -> Option<Asset> {
+        let mut assets = self.live_assets();
+        let asset = assets.get_mut(asset_id)?;
+        asset.last_used = Utc::now();
+        Some(asset.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_are_shared_with_request_workspaces() {
+        let store = UploadStore::new();
+        let upload = Workspace::create().unwrap();
+        std::fs::write(upload.input_path(), b"RIFF").unwrap();
+        let asset = store.insert(upload, 4);
+
+        let stored = store.get(&asset.asset_id).unwrap();
+        assert_eq!(stored.size_bytes, 4);
+        let request = Workspace::create().unwrap();
+        stored.link_into(&request).unwrap();
+        assert_eq!(std::fs::read(request.input_path()).unwrap(), b"RIFF");
+
+        // The request's workspace going away leaves the asset in place
+        drop(request);
+        let again = Workspace::create().unwrap();
+        store.get(&asset.asset_id).unwrap().link_into(&again).unwrap();
+        assert!(again.input_path().exists());
+        assert!(store.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_expired_assets_are_gone_on_lookup() {
+        let store = UploadStore::new();
+        let asset = store.insert(Workspace::create().unwrap(), 0);
+
+        let stale = Utc::now() - Duration::minutes(ASSET_TTL_MINUTES + 1);
+        store.assets.lock().unwrap().get_mut(&asset.asset_id).unwrap().last_used = stale;
+        assert!(store.get(&asset.asset_id).is_none());
+    }
+}
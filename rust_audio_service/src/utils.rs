@@ -1,16 +1,20 @@
-use std::path::PathBuf;
+use std::path::Path;
 use std::fs::File;
 use std::io::Write;
 use zip::{write::FileOptions, ZipWriter};
-use log::warn;
 
-use crate::processors::ProcessingResult;
+use crate::audio::DecodeOptions;
+use crate::errors::AudioResult;
+use crate::processors::{AudioProcessor, ProcessorConfig, ProcessingResult};
+use crate::workspace::Workspace;
 
-pub fn create_zip_from_result(result: &ProcessingResult, zip_path: &str) -> std::io::Result<()> {
+pub fn create_zip_from_result(result: &ProcessingResult, zip_path: &Path) -> std::io::Result<()> {
     let file = File::create(zip_path)?;
     let mut zip = ZipWriter::new(file);
+    // A fixed timestamp keeps the archive byte-identical for identical splices
     let options: FileOptions<'_, ()> = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Stored);
+        .compression_method(zip::CompressionMethod::Stored)
+        .last_modified_time(zip::DateTime::default());
 
     for (i, path) in result.files.iter().enumerate() {
         // Use the actual filename from the path, or create a generic name if needed
@@ -28,25 +32,60 @@ pub fn create_zip_from_result(result: &ProcessingResult, zip_path: &str) -> std:
     Ok(())
 }
 
-pub fn cleanup_temp_files(input_file: &str, splice_files: &[PathBuf], _zip_file: &str) {
-    // Remove input file
-    if let Err(e) = std::fs::remove_file(input_file) {
-        warn!("Failed to remove input file {}: {}", input_file, e);
+/// Process the upload in `workspace` and read back the ZIP of the result
+pub fn process_to_zip(
+    processor: &dyn AudioProcessor,
+    workspace: &Workspace,
+    config: &ProcessorConfig,
+    options: DecodeOptions,
+) -> AudioResult<(ProcessingResult, Vec<u8>)> {
+    let result = processor.process(&workspace.input_path(), workspace, config, options)?;
+    let zip_path = workspace.zip_path();
+    create_zip_from_result(&result, &zip_path)?;
+    let file_contents = std::fs::read(&zip_path)?;
+    Ok((result, file_contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioBuffer, AudioSource, PcmFormat};
+    use crate::processors::SpliceConfig;
+    use crate::processors::splice::SpliceProcessor;
+
+    /// Splice a 2 second ramp with `seed`, returning the ZIP and where each splice starts in seconds
+    fn splice_ramp(seed: u64) -> (Vec<u8>, Vec<f64>) {
+        let workspace = Workspace::create().unwrap();
+        let frames = 16_000;
+        let ramp = AudioBuffer {
+            sample_rate: 8000,
+            channels: 1,
+            format: PcmFormat::Float32,
+            samples: (0..frames).map(|frame| frame as f32 / frames as f32).collect(),
+        };
+        ramp.write_wav(&workspace.input_path(), PcmFormat::Float32).unwrap();
+
+        let config = ProcessorConfig::Splice(SpliceConfig { seed: Some(seed), ..SpliceConfig::new(0.25, 4) });
+        let (result, zip) = process_to_zip(&SpliceProcessor::new(), &workspace, &config, DecodeOptions::default()).unwrap();
+        let starts = result.files.iter()
+            .map(|file| AudioSource::open(file).unwrap().decode().unwrap().samples[0] as f64 * 2.0)
+            .collect();
+        (zip, starts)
     }
-    
-    // Remove splice files
-    for file in splice_files {
-        if let Err(e) = std::fs::remove_file(file) {
-            warn!("Failed to remove splice file {:?}: {}", file, e);
-        }
+
+    #[test]
+    fn test_same_seed_gives_byte_identical_zips() {
+        // Each run has its own workspace, so nothing from the path or clock may reach the archive
+        let (first, _) = splice_ramp(42);
+        let (second, _) = splice_ramp(42);
+        assert_eq!(first, second);
     }
-    
-    // Remove output directory if empty
-    if let Some(parent) = splice_files.first().and_then(|p| p.parent()) {
-        if let Err(e) = std::fs::remove_dir(parent) {
-            warn!("Failed to remove output directory {:?}: {}", parent, e);
-        }
+
+    #[test]
+    fn test_different_seed_moves_the_splices() {
+        let (first_zip, first_starts) = splice_ramp(42);
+        let (second_zip, second_starts) = splice_ramp(43);
+        assert_ne!(first_starts, second_starts);
+        assert_ne!(first_zip, second_zip);
     }
-    
-    log::info!("Cleanup completed for processing session");
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use uuid::Uuid;
+
+use crate::errors::{AudioError, AudioResult};
+
+/// A private scratch directory for a single request or job.
+///
+/// Every upload gets its own directory under the system temp dir, so concurrent
+/// requests never share input, output or ZIP paths. The directory and everything
+/// in it is removed when the `Workspace` is dropped, including on error paths.
+#[derive(Debug)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// Allocate a new workspace under `<temp_dir>/yt-sampler/<uuid>`
+    pub fn create() -> AudioResult<Self> {
+        Self::create_in(&std::env::temp_dir().join("yt-sampler"))
+    }
+
+    /// Allocate a new workspace under the given base directory
+    pub fn create_in(base: &Path) -> AudioResult<Self> {
+        let root = base.join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(root.join("output")).map_err(AudioError::IoError)?;
+
+        info!("Created workspace {:?}", root);
+        Ok(Self { root })
+    }
+
+    /// Where the uploaded source file is stored
+    pub fn input_path(&self) -> PathBuf {
+        self.root.join("input")
+    }
+
+    /// Directory processors write their output files into
+    pub fn output_dir(&self) -> PathBuf {
+        self.root.join("output")
+    }
+
+    /// Where the ZIP archive of the processing result is built
+    pub fn zip_path(&self) -> PathBuf {
+        self.root.join("result.zip")
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.root) {
+            Ok(()) => info!("Removed workspace {:?}", self.root),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => warn!("Failed to remove workspace {:?}: {}", self.root, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioSource;
+
+    /// An empty base directory of its own, so the test can see exactly what is left behind
+    fn test_base() -> PathBuf {
+        let base = std::env::temp_dir().join("yt-sampler-tests").join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    fn entries(base: &Path) -> usize {
+        std::fs::read_dir(base).unwrap().count()
+    }
+
+    #[test]
+    fn test_drop_removes_the_directory() {
+        let base = test_base();
+        let workspace = Workspace::create_in(&base).unwrap();
+        std::fs::write(workspace.input_path(), b"input").unwrap();
+        std::fs::write(workspace.output_dir().join("splice_0.wav"), b"output").unwrap();
+        std::fs::write(workspace.zip_path(), b"zip").unwrap();
+        assert_eq!(entries(&base), 1);
+
+        drop(workspace);
+        assert_eq!(entries(&base), 0);
+        std::fs::remove_dir(base).unwrap();
+    }
+
+    #[test]
+    fn test_drop_removes_the_directory_when_a_request_fails() {
+        fn failing_request(base: &Path) -> AudioResult<()> {
+            let workspace = Workspace::create_in(base)?;
+            std::fs::write(workspace.input_path(), b"not audio")?;
+            AudioSource::open(&workspace.input_path())?.decode()?;
+            Ok(())
+        }
+
+        let base = test_base();
+        assert!(matches!(failing_request(&base), Err(AudioError::InvalidFormat(_))));
+        assert_eq!(entries(&base), 0);
+        std::fs::remove_dir(base).unwrap();
+    }
+}
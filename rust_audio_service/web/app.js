@@ -107,8 +107,9 @@ class AudioProcessor {
 
     handleFile(file) {
         // Validate file type
-        if (!file.name.toLowerCase().endsWith('.wav')) {
-            this.showError('Please select a WAV file. Other formats are not currently supported.');
+        const supportedExtensions = ['.wav', '.mp3', '.m4a', '.aac', '.flac', '.ogg', '.opus'];
+        if (!supportedExtensions.some(ext => file.name.toLowerCase().endsWith(ext))) {
+            this.showError('Please select a WAV, MP3, AAC/M4A, FLAC, Ogg Vorbis or Ogg Opus file.');
             return;
         }
 